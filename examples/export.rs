@@ -0,0 +1,91 @@
+//! Matches two episodes and prints the intro and credits they share as JSON markers and as
+//! ffmpeg metadata chapters.
+//!
+//! ```sh
+//! cargo run --example export -- <path to ffmpeg> <path to tmp> <target> <reference>
+//! ```
+use slog::o;
+use slog::Drain;
+
+use sectionizer::config::SectionizerConfig;
+use sectionizer::export::Marker;
+use sectionizer::export::MarkerKind;
+use sectionizer::Sectionizer;
+
+#[tokio::main]
+async fn main() {
+    let decorator = slog_term::TermDecorator::new().build();
+    let drain = slog_term::FullFormat::new(decorator).build().fuse();
+    let drain = slog_async::Async::new(drain).build().fuse();
+    let logger = slog::Logger::root(drain, o!());
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 4 {
+        eprintln!("Usage: export <path to ffmpeg> <path to tmp> <target> <reference>");
+        return;
+    }
+
+    let (ffmpeg, tmp, target, reference) = (&args[0], &args[1], &args[2], &args[3]);
+
+    let openings = SectionizerConfig {
+        ffmpeg: ffmpeg.clone(),
+        cache_dir: tmp.clone(),
+        precise_boundaries: true,
+        ..SectionizerConfig::openings_preset()
+    };
+
+    let credits = SectionizerConfig {
+        ffmpeg: ffmpeg.clone(),
+        cache_dir: tmp.clone(),
+        ..SectionizerConfig::credits_preset()
+    };
+
+    let mut markers = Vec::new();
+
+    for (kind, config) in [
+        (MarkerKind::Intro, openings),
+        (MarkerKind::Credits, credits),
+    ] {
+        let mut sectionizer = Sectionizer::from_config(logger.clone(), config);
+
+        let (sections, _) = match sectionizer.categorize(target, reference, false).await {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Failed to categorize {:?}: {}", kind, e);
+                return;
+            }
+        };
+
+        // the longest section is the most likely to be the actual intro or credits.
+        markers.extend(
+            sections
+                .to_markers(kind)
+                .into_iter()
+                .max_by_key(|x| x.end - x.start),
+        );
+    }
+
+    println!("{}", serde_json::to_string_pretty(&markers).unwrap());
+    println!();
+    print!("{}", to_chapters(&markers));
+}
+
+/// Formats `markers` as chapters of an ffmpeg metadata file, which can be muxed back into the
+/// file with `ffmpeg -i <file> -i <metadata> -map_metadata 1 -codec copy <output>`.
+fn to_chapters(markers: &[Marker]) -> String {
+    let mut chapters = String::from(";FFMETADATA1\n");
+
+    for marker in markers {
+        let title = match marker.kind {
+            MarkerKind::Intro => "Intro",
+            MarkerKind::Credits => "Credits",
+        };
+
+        chapters.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1\nSTART={}\nEND={}\ntitle={}\n",
+            marker.start, marker.end, title
+        ));
+    }
+
+    chapters
+}
@@ -0,0 +1,74 @@
+//! Times reading two hours of 24 fps frames from a pipe through buffers of different sizes, the
+//! way frames are read from ffmpeg with `read_buffer_size`. The pipe is fed by `head -c` from
+//! `/dev/zero`, so only the reads are measured and not the decoding.
+//!
+//! ```sh
+//! cargo run --release --example read_buffer
+//! ```
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::BufReader;
+use tokio::process::Command;
+
+use sectionizer::config::SectionizerConfig;
+
+const FRAMES: usize = 2 * 3600 * 24;
+const RUNS: usize = 5;
+
+async fn read_frames(mut stream: impl AsyncRead + Unpin, frame_len: usize) -> usize {
+    let mut frame = vec![0; frame_len];
+    let mut frames = 0;
+
+    while stream.read_exact(&mut frame).await.is_ok() {
+        frames += 1;
+    }
+
+    frames
+}
+
+async fn time_reads(buffer_size: usize, frame_len: usize) -> Duration {
+    let mut child = Command::new("head")
+        .args(["-c", &(frame_len * FRAMES).to_string(), "/dev/zero"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn head");
+    let stdout = child.stdout.take().expect("head has no stdout");
+
+    let started = Instant::now();
+    let frames = read_frames(BufReader::with_capacity(buffer_size, stdout), frame_len).await;
+    let elapsed = started.elapsed();
+
+    assert_eq!(frames, FRAMES);
+    let _ = child.wait().await;
+
+    elapsed
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let config = SectionizerConfig::default();
+    let (width, height) = config.frame_size;
+    let frame_len = width as usize * height as usize * 3;
+
+    println!(
+        "{} frames of {} bytes, best of {} runs",
+        FRAMES, frame_len, RUNS
+    );
+
+    for buffer_size in [0, 8 * 1024, 64 * 1024, config.read_buffer_size] {
+        let mut best = Duration::MAX;
+        for _ in 0..RUNS {
+            best = best.min(time_reads(buffer_size, frame_len).await);
+        }
+
+        println!(
+            "{:>8} bytes {:>8.1} ms",
+            buffer_size,
+            best.as_secs_f64() * 1000.0
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Serves `categorize_events` over server-sent events, so that a browser can follow a run and
+//! receive the sections as soon as they are found.
+//!
+//! ```sh
+//! cargo run --example sse -- <path to ffmpeg> <path to tmp>
+//! curl -N 'http://127.0.0.1:3030/sections?file1=<target>&file2=<reference>'
+//! ```
+//!
+//! Closing the connection cancels the run. The server only listens on localhost as it reads any
+//! file it is asked to.
+use std::convert::Infallible;
+
+use futures::stream;
+use futures::Stream;
+use serde::Deserialize;
+use serde_json::json;
+use slog::o;
+use slog::Drain;
+use tokio::sync::mpsc;
+use warp::sse::Event;
+use warp::Filter;
+
+use sectionizer::config::CategorizeOptions;
+use sectionizer::config::SectionizerConfig;
+use sectionizer::events::SectionizerEvent;
+use sectionizer::Sectionizer;
+
+#[derive(Deserialize)]
+struct Query {
+    file1: String,
+    file2: String,
+    #[serde(default)]
+    reverse: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let decorator = slog_term::TermDecorator::new().build();
+    let drain = slog_term::FullFormat::new(decorator).build().fuse();
+    let drain = slog_async::Async::new(drain).build().fuse();
+    let logger = slog::Logger::root(drain, o!());
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 2 {
+        eprintln!("Usage: sse <path to ffmpeg> <path to tmp>");
+        return;
+    }
+
+    let config = SectionizerConfig {
+        ffmpeg: args[0].clone(),
+        cache_dir: args[1].clone(),
+        ..Default::default()
+    };
+
+    let route = warp::path("sections")
+        .and(warp::query::<Query>())
+        .map(move |query: Query| {
+            let events = categorize(logger.clone(), config.clone(), query);
+            warp::sse::reply(warp::sse::keep_alive().stream(events))
+        });
+
+    warp::serve(route).run(([127, 0, 0, 1], 3030)).await;
+}
+
+/// Starts matching the files of `query` and returns the stream of its events. The run stops as
+/// soon as the stream is dropped, which warp does once the client disconnects.
+fn categorize(
+    logger: slog::Logger,
+    config: SectionizerConfig,
+    query: Query,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let (tx, rx) = mpsc::channel(32);
+
+    // the futures of a run hold on to the hasher and trees, which can't be sent across threads,
+    // so every run gets a thread and runtime of its own.
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to build runtime");
+
+        runtime.block_on(async move {
+            let mut sectionizer = Sectionizer::from_config(logger.clone(), config);
+            let closed = tx.clone();
+
+            tokio::select! {
+                _ = sectionizer.categorize_events(
+                    query.file1,
+                    query.file2,
+                    query.reverse,
+                    CategorizeOptions::default(),
+                    tx,
+                ) => {}
+                _ = closed.closed() => slog::info!(logger, "Client disconnected, cancelling"),
+            }
+        });
+    });
+
+    stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        Some((Ok(to_sse(event)), rx))
+    })
+}
+
+fn to_sse(event: SectionizerEvent) -> Event {
+    let (name, data) = match event {
+        SectionizerEvent::Started { file1, file2 } => {
+            ("started", json!({ "file1": file1, "file2": file2 }))
+        }
+        SectionizerEvent::Progress { file, frames } => {
+            ("progress", json!({ "file": file, "frames": frames }))
+        }
+        SectionizerEvent::FileDone { file, frames } => {
+            ("file_done", json!({ "file": file, "frames": frames }))
+        }
+        SectionizerEvent::SectionsReady(sections) => {
+            let (sections1, sections2) = *sections;
+            ("sections", json!([sections1, sections2]))
+        }
+        SectionizerEvent::Error(e) => ("error", json!({ "error": e })),
+    };
+
+    Event::default().event(name).data(data.to_string())
+}
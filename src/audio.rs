@@ -0,0 +1,179 @@
+//! Fingerprints of audio tracks, for inputs which have no video to compare.
+//!
+//! Audio is decoded to mono PCM at [`SAMPLE_RATE`], mixed down as [`AudioMix`] says, and cut into
+//! overlapping windows, [`FPS`] of them per second. Every window is hashed into 32 bits, each
+//! telling whether the energy difference between two neighbouring frequency bands grew or shrank
+//! since the previous window, in the fashion of Haitsma and Kalker. The hashes are wrapped in [`Frame`]s so that audio goes
+//! through the same matching and grouping as video, at a frame rate of `FPS`.
+use std::f32::consts::PI;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+
+use crate::config::AudioMix;
+use crate::hash::FrameHash;
+use crate::hash::HashWidth;
+use crate::Frame;
+
+/// Rate audio is resampled to before fingerprinting.
+pub const SAMPLE_RATE: usize = 5512;
+/// Number of fingerprints taken per second of audio.
+pub const FPS: f64 = (SAMPLE_RATE / HOP) as f64;
+/// Number of samples between the starts of two windows.
+const HOP: usize = 689;
+/// Number of samples a window spans, so that consecutive windows overlap by a little.
+const WINDOW: usize = 512;
+/// Default frequency range in Hz the bands are spread over, where most of the energy of speech and
+/// music lies.
+pub const FREQ_RANGE: (f32, f32) = (300.0, 2000.0);
+/// Number of bands, every pair of neighbouring bands yields one bit of the hash.
+const BANDS: usize = 33;
+/// Windows whose RMS is below this are silence, which would match any other silence, so they are
+/// not fingerprinted.
+const SILENCE_RMS: f32 = 64.0;
+
+/// Returns the ffmpeg arguments decoding the first audio stream of `file` into what
+/// [`read_samples`] expects, with `tt` and `sseof` bounding the window like for video.
+pub(crate) fn ffmpeg_args(
+    file: &str,
+    tt: Option<usize>,
+    sseof: Option<i64>,
+    mix: AudioMix,
+) -> Vec<String> {
+    // ffmpeg's stderr is only read once it exits, progress output would fill up the pipe.
+    let mut args = vec!["-v".to_string(), "error".into(), "-nostats".into()];
+
+    if let Some(sseof) = sseof {
+        args.extend(["-sseof".into(), (-sseof).to_string()]);
+    }
+
+    args.extend(["-i".into(), file.to_string(), "-map".into(), "0:a:0".into()]);
+
+    if let (Some(tt), None) = (tt, sseof) {
+        args.extend(["-t".into(), tt.to_string()]);
+    }
+
+    match mix {
+        AudioMix::Mono => args.extend(["-ac".into(), "1".into()]),
+        AudioMix::Side => args.extend([
+            "-af".into(),
+            "aformat=channel_layouts=stereo,pan=mono|c0=0.5*FL-0.5*FR".into(),
+        ]),
+    }
+
+    args.extend(["-vn", "-ar"].iter().map(ToString::to_string));
+    args.push(SAMPLE_RATE.to_string());
+    args.extend(["-f", "s16le", "-"].iter().map(ToString::to_string));
+    args
+}
+
+/// Reads all of `stream`, signed 16 bit little endian PCM, into samples.
+pub(crate) async fn read_samples(mut stream: impl AsyncRead + Unpin) -> Vec<i16> {
+    let mut buf = Vec::new();
+    let _ = stream.read_to_end(&mut buf).await;
+
+    buf.chunks_exact(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect()
+}
+
+/// Returns the fingerprints of `samples`, mono PCM at [`SAMPLE_RATE`], with the bands spread over
+/// `freq_range` in Hz. The index of every fingerprint is that of its window, counted at [`FPS`].
+/// Silent windows, along with the window following them, are left out.
+pub fn fingerprint(samples: &[i16], freq_range: (f32, f32)) -> Vec<Frame> {
+    if samples.len() < WINDOW {
+        return Vec::new();
+    }
+
+    let bands = bands(freq_range);
+    let bins = bands[0].0..bands[BANDS - 1].1;
+
+    let hann = (0..WINDOW)
+        .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / WINDOW as f32).cos())
+        .collect::<Vec<_>>();
+    // only the bins covered by a band are needed, which is cheap enough to compute directly.
+    let twiddles = bins
+        .clone()
+        .map(|k| {
+            (0..WINDOW)
+                .map(|n| {
+                    let w = 2.0 * PI * ((k * n) % WINDOW) as f32 / WINDOW as f32;
+                    (w.cos(), w.sin())
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut frames = Vec::new();
+    let mut previous: Option<[f32; BANDS]> = None;
+
+    for (idx, start) in (0u64..).zip((0..=samples.len() - WINDOW).step_by(HOP)) {
+        let chunk = &samples[start..start + WINDOW];
+
+        let rms = (chunk.iter().map(|x| (*x as f32).powi(2)).sum::<f32>() / WINDOW as f32).sqrt();
+        if rms < SILENCE_RMS {
+            previous = None;
+            continue;
+        }
+
+        let windowed = chunk
+            .iter()
+            .zip(hann.iter())
+            .map(|(x, w)| *x as f32 * w)
+            .collect::<Vec<_>>();
+        let power = twiddles
+            .iter()
+            .map(|twiddles| {
+                let (re, im) = twiddles
+                    .iter()
+                    .zip(windowed.iter())
+                    .fold((0.0, 0.0), |(re, im), ((cos, sin), x)| {
+                        (re + x * cos, im - x * sin)
+                    });
+                re * re + im * im
+            })
+            .collect::<Vec<f32>>();
+
+        let mut energy = [0.0; BANDS];
+        for (energy, (lo, hi)) in energy.iter_mut().zip(bands.iter()) {
+            *energy = power[lo - bins.start..hi - bins.start].iter().sum();
+        }
+
+        if let Some(previous) = previous {
+            let bits = (0..BANDS - 1)
+                .filter(|m| (energy[*m] - energy[m + 1]) - (previous[*m] - previous[m + 1]) > 0.0)
+                .map(|m| m as u32);
+
+            frames.push(Frame::new(
+                FrameHash::from_bits(HashWidth::Bits64, bits),
+                idx,
+            ));
+        }
+
+        previous = Some(energy);
+    }
+
+    frames
+}
+
+/// Returns the range of DFT bins of every band, log spaced over `(min_freq, max_freq)` capped at
+/// the Nyquist frequency. Every band covers at least one bin, which widens ranges too narrow for
+/// that upwards.
+fn bands((min_freq, max_freq): (f32, f32)) -> Vec<(usize, usize)> {
+    let bin = |freq: f32| (freq * WINDOW as f32 / SAMPLE_RATE as f32).round() as usize;
+
+    let max_freq = max_freq.min(SAMPLE_RATE as f32 / 2.0).max(1.0);
+    let min_freq = min_freq.clamp(1.0, max_freq);
+    let edge = |k: usize| min_freq * (max_freq / min_freq).powf(k as f32 / BANDS as f32);
+
+    let mut bands = Vec::with_capacity(BANDS);
+    let mut lo = bin(min_freq);
+
+    for k in 1..=BANDS {
+        let hi = bin(edge(k)).max(lo + 1);
+        bands.push((lo, hi));
+        lo = hi;
+    }
+
+    bands
+}
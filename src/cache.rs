@@ -0,0 +1,127 @@
+//! On-disk cache of the frames extracted from files, so that files seen before are matched
+//! without decoding them again, see
+//! [`Sectionizer::categorize_cached`](crate::Sectionizer::categorize_cached).
+//!
+//! Every entry records what it was extracted from and with, and is only used if that still holds:
+//! the file must have the same size and modification time, and the window and every setting
+//! affecting the hashes must be unchanged. Anything else is a miss, the stale entry is then
+//! overwritten.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::SectionizerConfig;
+use crate::config::Similarity;
+use crate::config::Window;
+use crate::Frame;
+use crate::IgnoredFrames;
+use crate::Result;
+
+/// What a cache entry was extracted from and with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub file: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Modification time of the file in nanoseconds since the epoch, if the platform has it.
+    pub modified: Option<u128>,
+    pub window: Window,
+    pub fps: f64,
+    /// The settings affecting the hashes of the frames, in their debug representation.
+    pub settings: String,
+}
+
+impl CacheKey {
+    /// Returns the key `file` is cached under when extracted with `config` at `fps`. Returns `None` if it
+    /// can't be cached: when it isn't a local file, or when a custom `hasher` is configured,
+    /// which can't be told apart from another one.
+    pub(crate) fn new(
+        config: &SectionizerConfig,
+        file: &str,
+        window: Window,
+        fps: f64,
+    ) -> Option<Self> {
+        if config.hasher.is_some() {
+            return None;
+        }
+
+        let metadata = std::fs::metadata(file).ok().filter(|x| x.is_file())?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .map(|x| x.as_nanos());
+
+        let settings = format!(
+            "{} {:?} {:?} {:?} {:?} {} {} {} {} {} {:?} {:?} {:?} {} {}",
+            env!("CARGO_PKG_VERSION"),
+            config.hash_width,
+            config.hash_alg,
+            config.resize_filter,
+            config.frame_size,
+            config.hash_stride,
+            config.skip_solid_frames,
+            config.solid_frame_threshold,
+            config.black_luma_threshold,
+            config.crop_margin,
+            config.luma_weights,
+            config.deinterlace,
+            config.tonemap,
+            config.similarity != Similarity::Hash,
+            config.max_frames,
+        );
+
+        Some(Self {
+            file: file.to_string(),
+            size: metadata.len(),
+            modified,
+            window,
+            fps,
+            settings,
+        })
+    }
+}
+
+/// The frames extracted from the window of a file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CachedFrames {
+    pub key: CacheKey,
+    pub frames: Vec<Frame>,
+    pub ignored: IgnoredFrames,
+}
+
+/// Returns the path of the entry of `key` in `dir`. Entries are named after the file and window
+/// only, so that a stale entry is replaced rather than kept alongside.
+fn path(dir: &Path, key: &CacheKey) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    key.file.hash(&mut hasher);
+    format!("{:?}", key.window).hash(&mut hasher);
+
+    dir.join(format!("{:016x}.json", hasher.finish()))
+}
+
+/// Returns the entry of `key` in `dir` if there is one and it is still valid.
+pub(crate) fn load(dir: &Path, key: &CacheKey) -> Result<Option<CachedFrames>> {
+    let data = match std::fs::read(path(dir, key)) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let entry: CachedFrames = serde_json::from_slice(&data)?;
+
+    Ok(Some(entry).filter(|x| x.key == *key))
+}
+
+pub(crate) fn save(dir: &Path, entry: &CachedFrames) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(path(dir, &entry.key), serde_json::to_vec(entry)?)?;
+
+    Ok(())
+}
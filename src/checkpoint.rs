@@ -0,0 +1,43 @@
+//! Resumable extraction, for analyses long enough to need interrupting.
+use crate::config::Window;
+use crate::Frame;
+use crate::IgnoredFrames;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Frames extracted so far from the window of a file. A checkpoint can be persisted and handed
+/// back to [`Sectionizer::extract_resumable`](crate::Sectionizer::extract_resumable) later to
+/// carry on where extraction stopped.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub file: String,
+    /// Window being extracted.
+    pub window: Window,
+    /// Frame rate used to map frame indices to seek positions.
+    pub fps: f64,
+    /// Frames extracted so far, ready for
+    /// [`Sectionizer::categorize_frames`](crate::Sectionizer::categorize_frames).
+    pub frames: Vec<Frame>,
+    /// Frames that were dropped before matching. Frames decoded twice around a resume point may
+    /// be counted twice.
+    pub ignored: IgnoredFrames,
+    /// Index, within the window, of the next frame to extract.
+    pub next_idx: u64,
+    /// Whether the whole window has been extracted.
+    pub done: bool,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(file: String, window: Window, fps: f64) -> Self {
+        Self {
+            file,
+            window,
+            fps,
+            frames: Vec::new(),
+            ignored: IgnoredFrames::default(),
+            next_idx: 0,
+            done: false,
+        }
+    }
+}
@@ -0,0 +1,627 @@
+//! Tunables controlling how a [`Sectionizer`](crate::Sectionizer) extracts and matches frames.
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::hash::FrameHasher;
+use crate::postprocess::SectionPostProcessor;
+
+/// Configuration for a [`Sectionizer`](crate::Sectionizer).
+///
+/// The defaults reproduce the behaviour of the crate before any of these knobs existed.
+#[derive(Clone, Debug)]
+pub struct SectionizerConfig {
+    /// Maximum hamming distance between two frame hashes for them to be considered a match. A
+    /// distance of exactly `hash_max_dist` matches unless `distance_bound` says otherwise.
+    pub hash_max_dist: isize,
+    /// When set, `hash_max_dist` is ignored and a threshold is picked for every pair of files from
+    /// the distribution of their nearest-neighbour distances. See
+    /// [`Sectionizer::auto_threshold`](crate::Sectionizer::auto_threshold).
+    pub auto_threshold: bool,
+    /// When set, `hash_max_dist` and `auto_threshold` are ignored and the threshold is picked
+    /// out of these distances as the one giving the most stable section. See
+    /// [`Sectionizer::sweep_threshold`](crate::Sectionizer::sweep_threshold).
+    pub threshold_sweep: Option<std::ops::RangeInclusive<isize>>,
+    /// Path to a `ffprobe` binary, used to look up container metadata such as durations.
+    pub ffprobe: String,
+    /// Number of seconds analyzed from the start of each file.
+    pub head_secs: usize,
+    /// Number of seconds analyzed from the end of each file in reverse mode.
+    pub tail_secs: usize,
+    /// Drop frames which are a single solid color (fades to black, title cards) before matching.
+    /// These carry no information and match across unrelated content.
+    pub skip_solid_frames: bool,
+    /// How far, per color channel, the pixels of a frame may stray for `skip_solid_frames` to
+    /// still treat it as solid. `0` only drops frames made of a single exact color.
+    pub solid_frame_threshold: u8,
+    /// Luma, as in Rec. 601, which no pixel of a solid frame may exceed for it to count as black
+    /// rather than solid. Encodes with dark grey blacks can be caught by raising it.
+    pub black_luma_threshold: u8,
+    /// Minimum number of seconds between two occurrences of a segment for
+    /// [`Sectionizer::self_sections`](crate::Sectionizer::self_sections) to treat them as a repeat.
+    pub self_match_min_gap_secs: u64,
+    /// Filter used by `img_hash` when resizing frames internally before hashing. This is applied
+    /// on top of the scaling ffmpeg already does.
+    pub resize_filter: img_hash::FilterType,
+    /// Frame rate assumed when mapping frame indices to seconds.
+    pub fps: f64,
+    /// Whether to probe the frame rate of each file with ffprobe, falling back to `fps` if that
+    /// fails. The `fps1` and `fps2` of [`CategorizeOptions`] take precedence over both.
+    pub detect_fps: bool,
+    /// Path to a `ffmpeg` binary, used by [`Sectionizer::from_config`](crate::Sectionizer::from_config).
+    pub ffmpeg: String,
+    /// Directory where ffmpeg sessions keep their temporary data, used by
+    /// [`Sectionizer::from_config`](crate::Sectionizer::from_config).
+    pub cache_dir: String,
+    /// Latest second of the file at which a section may start to be picked by
+    /// [`Sectionizer::best_opening`](crate::Sectionizer::best_opening).
+    pub opening_max_start_secs: u128,
+    /// Which section [`Sectionizer::best_opening`](crate::Sectionizer::best_opening) picks when
+    /// several qualify.
+    pub section_preference: SectionPreference,
+    /// Whether individual frames or whole scenes are matched against each other.
+    pub granularity: Granularity,
+    /// Fraction of the width and height cropped off each edge of a frame before hashing, so that
+    /// burnt-in timecodes or channel logos in the corners don't affect matching. `0.1` is a good
+    /// starting point for broadcast captures. Values are capped at `0.45`.
+    pub crop_margin: f32,
+    /// Duration in seconds of the buckets matched frames are grouped into. Smaller buckets give
+    /// tighter section boundaries but need denser matches to fill.
+    pub bucket_secs: f64,
+    /// Maximum gap in seconds between two matched buckets for them to be part of the same section.
+    /// This applies while grouping raw buckets, before any post-processing, see
+    /// `section_merge_gap_secs` for joining finished sections and `min_section_gap_secs` for
+    /// keeping close ones apart.
+    pub merge_gap_secs: f64,
+    /// Maximum gap in seconds between two finished sections for them to be merged into one, once
+    /// every other post-processing step ran. This joins sections which the grouping kept apart,
+    /// such as the halves of an opening split by a scene the other file doesn't have, without
+    /// loosening `merge_gap_secs` for every bucket. `None` never merges sections.
+    pub section_merge_gap_secs: Option<f64>,
+    /// Minimum gap in seconds without matches separating two distinct sections. Such a gap always
+    /// ends a section while grouping buckets, even where `merge_gap_secs` would bridge it, so a
+    /// recap followed by the opening after a short title card comes out as two sections. Gaps
+    /// shorter than this are bridged as `merge_gap_secs` allows, which makes a value above
+    /// `merge_gap_secs` meaningless. To join close sections explicitly instead, leave this unset
+    /// and use `section_merge_gap_secs`. `None` only applies `merge_gap_secs`.
+    pub min_section_gap_secs: Option<f64>,
+    /// Always analyze the tail of the files in `categorize`, as if `reverse` was passed.
+    pub reverse: bool,
+    /// Order the matched frames of a window are walked in to find sections. See
+    /// [`ScanDirection`].
+    pub scan_direction: ScanDirection,
+    /// Maximum number of frames decoded per file, anything past this is dropped with a warning.
+    /// This bounds memory and time spent on pathological inputs.
+    pub max_frames: u64,
+    /// Warn when the duration of one file is more than this many times the duration of the other,
+    /// as comparing such files rarely makes sense. This costs two extra ffprobe runs per pair, so
+    /// it is off by default, `None` skips probing the durations altogether.
+    pub max_duration_ratio: Option<f64>,
+    /// When the durations differ by more than `max_duration_ratio`, stretch the window of the
+    /// longer file so that both windows cover the same fraction of their file.
+    pub scale_windows: bool,
+    /// Width of the frame hashes. Wider hashes discriminate better between similar frames but
+    /// take more memory, `hash_max_dist` should be scaled up along with the width.
+    pub hash_width: crate::hash::HashWidth,
+    /// Width and height ffmpeg scales frames down to before they are hashed. Larger frames keep
+    /// detail telling similar scenes apart, at the cost of speed: frames of any other size than
+    /// the 18x16 of nightfall's profile are decoded by spawning ffmpeg directly, and read and
+    /// hashed at that size. Streams handed to `categorize_readers` have to match it.
+    pub frame_size: (u32, u32),
+    /// Algorithm used to hash frames.
+    pub hash_alg: img_hash::HashAlg,
+    /// Refine the start of every section backwards up to a second, to the nearest scene cut or
+    /// fade from black. Fade-ins often hash too differently to match, which would otherwise make
+    /// sections start a little late.
+    pub precise_boundaries: bool,
+    /// Fraction of the frames in the first second of a section which must have matched for
+    /// `precise_boundaries` to move its start, weaker starts are left as they are.
+    pub boundary_min_confidence: f64,
+    /// Directory the first `debug_frames` frames of every file are written to as PNGs, exactly as
+    /// they are hashed, for checking what ffmpeg delivers and how it is cropped. Frames of a file
+    /// go to a subdirectory named after the file and are named after their index and hash. This
+    /// is I/O heavy and meant for debugging only.
+    pub debug_frames_dir: Option<String>,
+    /// Number of frames written to `debug_frames_dir` per file.
+    pub debug_frames: u64,
+    /// Stop decoding the first file as soon as it contains a section satisfying these criteria,
+    /// which cuts latency when only the opening is of interest. Sections past that point, such as
+    /// end credits, are not found.
+    pub early_stop: Option<EarlyStop>,
+    /// Red, green and blue weights used to convert frames to the grayscale image that gets hashed.
+    /// `None` keeps the Rec. 709 conversion `img_hash` does by default. Content with strong single
+    /// color branding can match more consistently with that channel weighted down.
+    pub luma_weights: Option<[f32; 3]>,
+    /// Minimum number of seconds worth of frames each file needs for an empty result to be
+    /// trusted, see [`AnalysisStatus`](crate::AnalysisStatus).
+    pub min_analyzed_secs: f64,
+    /// How the timestamp of the first matched frame of a section is rounded to seconds.
+    pub start_rounding: Rounding,
+    /// How the timestamp of the last matched frame of a section is rounded to seconds. Flooring
+    /// starts and ceiling ends gives sections which fully contain their matched frames.
+    pub end_rounding: Rounding,
+    /// Analyze only this chapter of each file instead of the head or tail window. Files without
+    /// chapters, or without a matching one, fall back to the regular window.
+    pub chapter: Option<ChapterSelector>,
+    /// Trim the leading and trailing buckets of every section in which less than this fraction of
+    /// the frames matched, tightening sections padded by a few sparse matches. `None` keeps every
+    /// bucket.
+    pub edge_density: Option<f64>,
+    /// Minimum duration in seconds of the longest run of consecutive hashed frames which all
+    /// matched within a section for it to be kept. A real shared section is anchored by such a run,
+    /// while scattered matches adding up to the same count are not. `None` keeps every section.
+    pub min_run_secs: Option<f64>,
+    /// How the confidence of every section, and with it
+    /// [`SectionPreference::HighestConfidence`], is scored.
+    pub section_score: SectionScore,
+    /// Minimum number of matched frames across a whole section for it to be kept. This catches
+    /// sections made up of several sparse buckets which each barely have enough matches.
+    pub min_total_matches: usize,
+    /// How frames are compared with each other, `hash_max_dist` applies to the resulting
+    /// distance.
+    pub similarity: Similarity,
+    /// Only hash every `hash_stride`th decoded frame, the others are read and dropped. This cuts
+    /// the hashing time without relying on an ffmpeg filter, frame indices stay those of the
+    /// stream. `1` hashes every frame.
+    pub hash_stride: u64,
+    /// Collapse runs of consecutive frames with the same hash into their first frame before
+    /// indexing them for matching. Static shots such as title cards otherwise fill the tree with
+    /// copies of a single hash. Matches still report the frame of the run closest to the frame
+    /// being matched, so boundaries are unaffected.
+    pub dedupe_tree_frames: bool,
+    /// Maximum number of frames indexed for matching, which bounds the memory a tree takes for
+    /// long references. Larger frame vectors are subsampled uniformly, keeping every `n`th frame.
+    ///
+    /// A frame can only match one kept in the tree, so subsampling by `n` cuts the matches of every
+    /// bucket by about as much. Matched frames of one file still spread over its whole section,
+    /// so boundaries hardly move, but buckets falling below `min_bucket_matches` can split long
+    /// sections and drop short ones; keep `n` small or lower `min_total_matches` along with it.
+    /// `None` indexes every frame.
+    pub max_tree_frames: Option<usize>,
+    /// Analyze the whole of each file in `categorize` instead of `head_secs` or `tail_secs`. See
+    /// [`Window::Full`] for the settings this works best with.
+    pub full_file: bool,
+    /// Steps applied in order to the sections of every match, see
+    /// [`postprocess`](crate::postprocess). Defaults to
+    /// [`default_pipeline`](crate::postprocess::default_pipeline).
+    pub post_processors: Vec<Arc<dyn SectionPostProcessor>>,
+    /// Size in bytes of the buffer frames are read from ffmpeg through, so that a single read
+    /// from the pipe yields many frames. `0` reads every frame straight from the pipe.
+    pub read_buffer_size: usize,
+    /// Whether frames exactly `hash_max_dist` apart match. At the small thresholds hashes are
+    /// usually matched with, `<= 2` and `< 2` behave very differently.
+    pub distance_bound: DistanceBound,
+    /// Minimum fraction of the frames spanned by the candidate sections which matched, across all
+    /// of them. Below this every candidate is dropped and the result is
+    /// [`AnalysisStatus::Rejected`](crate::AnalysisStatus::Rejected), rather than reporting a few
+    /// sparse matches as a section. `None` keeps every candidate.
+    pub min_match_density: Option<f64>,
+    /// Decode the two files of a `categorize` call one after the other instead of running both
+    /// ffmpeg processes at once. This halves the peak load on constrained hardware at the cost of
+    /// wall clock time.
+    pub sequential_extraction: bool,
+    /// Log how many matches every bucket received and at which distances, before the buckets are
+    /// grouped into sections. See also
+    /// [`Sectionizer::inspect_groups`](crate::Sectionizer::inspect_groups).
+    pub debug_groups: bool,
+    /// Only keep the sections of either file which are corroborated by the sections of the other,
+    /// through the frames they were matched with. This drops one-sided matches, such as a
+    /// recurring frame matching all over a section of the other file. Has no effect when only one
+    /// direction is matched, as in `categorize_one`.
+    pub cross_validate: bool,
+    /// Hasher frames are hashed with after cropping and the `luma_weights` conversion. `None`
+    /// uses `img_hash` with `hash_alg`, `hash_width` and `resize_filter`.
+    pub hasher: Option<Arc<dyn FrameHasher>>,
+    /// Deinterlace frames before they are scaled down for hashing, as the comb artifacts of
+    /// interlaced sources differ from field to field and throw hashes off. Nightfall's profiles
+    /// can't take extra filters, so ffmpeg is spawned directly for the files this applies to.
+    pub deinterlace: Deinterlace,
+    /// Tonemap HDR sources to 8-bit SDR before they are scaled down for hashing. ffmpeg's plain
+    /// conversion to `rgb24` ignores the transfer function, so the frames of an HDR release come
+    /// out washed out and don't match the SDR release of the same episode. Like `deinterlace`,
+    /// ffmpeg is spawned directly for the files this applies to, and it must be built with
+    /// `zimg` for the `zscale` filter.
+    pub tonemap: Tonemap,
+    /// Maximum distance between two audio fingerprints for them to match, used instead of
+    /// `hash_max_dist` when inputs without video are matched on their audio. Audio fingerprints
+    /// are noisier than frame hashes, but as they only have 32 bits unrelated audio starts
+    /// matching well below the half of them.
+    pub audio_max_dist: isize,
+    /// Channels of the audio which are fingerprinted when inputs without video are matched.
+    pub audio_mix: AudioMix,
+    /// Frequency range in Hz the bands of audio fingerprints are spread over, capped at half of
+    /// [`audio::SAMPLE_RATE`](crate::audio::SAMPLE_RATE). Narrowing it to where the music of an
+    /// opening is loudest, together with [`AudioMix::Side`], helps dubs match the original.
+    pub audio_freq_range: (f32, f32),
+}
+
+impl Default for SectionizerConfig {
+    fn default() -> Self {
+        Self {
+            hash_max_dist: crate::HASH_MAX_DIST,
+            auto_threshold: false,
+            threshold_sweep: None,
+            ffprobe: "ffprobe".into(),
+            head_secs: 300,
+            tail_secs: 300,
+            skip_solid_frames: false,
+            solid_frame_threshold: 8,
+            black_luma_threshold: 8,
+            self_match_min_gap_secs: 30,
+            resize_filter: img_hash::FilterType::Lanczos3,
+            fps: 24.0,
+            detect_fps: false,
+            ffmpeg: "ffmpeg".into(),
+            cache_dir: "/tmp/streaming_cache".into(),
+            opening_max_start_secs: 240,
+            section_preference: SectionPreference::Longest,
+            granularity: Granularity::Frame,
+            crop_margin: 0.0,
+            bucket_secs: 1.0,
+            merge_gap_secs: 5.0,
+            section_merge_gap_secs: None,
+            min_section_gap_secs: None,
+            reverse: false,
+            scan_direction: ScanDirection::Forward,
+            max_frames: 500_000,
+            max_duration_ratio: None,
+            scale_windows: false,
+            hash_width: crate::hash::HashWidth::Bits128,
+            frame_size: (crate::IMG_W as u32, crate::IMG_H as u32),
+            hash_alg: crate::HASHER,
+            precise_boundaries: false,
+            boundary_min_confidence: 0.5,
+            debug_frames_dir: None,
+            debug_frames: 100,
+            early_stop: None,
+            luma_weights: None,
+            min_analyzed_secs: 10.0,
+            start_rounding: Rounding::Floor,
+            end_rounding: Rounding::Floor,
+            chapter: None,
+            edge_density: None,
+            min_run_secs: None,
+            section_score: SectionScore::Density,
+            min_total_matches: 0,
+            similarity: Similarity::Hash,
+            hash_stride: 1,
+            dedupe_tree_frames: false,
+            max_tree_frames: None,
+            full_file: false,
+            post_processors: crate::postprocess::default_pipeline(),
+            read_buffer_size: 256 * 1024,
+            distance_bound: DistanceBound::Inclusive,
+            min_match_density: None,
+            sequential_extraction: false,
+            debug_groups: false,
+            cross_validate: false,
+            hasher: None,
+            deinterlace: Deinterlace::Off,
+            tonemap: Tonemap::Off,
+            audio_max_dist: 4,
+            audio_mix: AudioMix::Mono,
+            audio_freq_range: crate::audio::FREQ_RANGE,
+        }
+    }
+}
+
+impl SectionizerConfig {
+    /// Returns the configured head window, or the tail window if `reverse` is set.
+    pub fn window(&self, reverse: bool) -> Window {
+        if self.full_file {
+            Window::Full
+        } else if reverse {
+            Window::Tail(self.tail_secs)
+        } else {
+            Window::Head(self.head_secs)
+        }
+    }
+
+    /// Defaults tuned for end credits. Credits are mostly text over black which hashes less
+    /// consistently than regular footage, so the threshold is looser and the black frames between
+    /// title cards, which would otherwise match anything, are dropped.
+    pub fn credits_preset() -> Self {
+        Self {
+            hash_max_dist: 4,
+            tail_secs: 180,
+            skip_solid_frames: true,
+            reverse: true,
+            ..Default::default()
+        }
+    }
+
+    /// Defaults trading speed for accuracy, for content where unrelated scenes look alike. Frames
+    /// are hashed from 32x32 thumbnails into 256 bit hashes, which keep apart scenes that the
+    /// default 18x16 frames and 128 bit hashes blur together. Hashing and matching the wider
+    /// hashes makes this noticeably slower and the frames take over three times the memory.
+    pub fn high_accuracy_preset() -> Self {
+        Self {
+            frame_size: (32, 32),
+            hash_width: crate::hash::HashWidth::Bits256,
+            hash_max_dist: 4,
+            ..Default::default()
+        }
+    }
+
+    /// Defaults tuned for openings, which usually sit in the first few minutes and are often
+    /// surrounded by fades to black.
+    pub fn openings_preset() -> Self {
+        Self {
+            head_secs: 240,
+            skip_solid_frames: true,
+            opening_max_start_secs: 180,
+            ..Default::default()
+        }
+    }
+}
+
+/// Criteria a section has to meet for [`SectionizerConfig::early_stop`] to stop decoding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EarlyStop {
+    /// Minimum duration of the section in seconds.
+    pub min_secs: f64,
+    /// Minimum fraction of the buckets of the section which must contain matches.
+    pub min_density: f64,
+}
+
+/// A span of a file to analyze.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Window {
+    /// The first `n` seconds of the file.
+    Head(usize),
+    /// The last `n` seconds of the file.
+    Tail(usize),
+    /// `secs` seconds of the file starting at second `start`.
+    Span { start: usize, secs: usize },
+    /// The whole file.
+    ///
+    /// Every frame of the file is decoded and kept in memory, which for a feature film is an
+    /// order of magnitude more work than a regular window. Unless `hash_stride` is set, the
+    /// `categorize` methods only hash every
+    /// [`FULL_FILE_HASH_STRIDE`](crate::FULL_FILE_HASH_STRIDE)th frame. `max_frames` still caps
+    /// the number of frames decoded, so raise it to cover the whole file. For full-file analysis
+    /// we recommend:
+    ///
+    /// - `granularity: Granularity::Scene`, which matches a single hash per second;
+    /// - `hash_stride` of `4` to `8`, depending on the frame rate;
+    /// - `max_frames` of at least the duration of the longest file times its frame rate.
+    Full,
+}
+
+impl Window {
+    /// Returns whether the window is measured from the end of the file.
+    pub fn is_tail(&self) -> bool {
+        matches!(self, Self::Tail(_))
+    }
+
+    /// Returns the window stretched by `factor`.
+    pub fn scale(self, factor: f64) -> Self {
+        match self {
+            Self::Head(secs) => Self::Head((secs as f64 * factor) as usize),
+            Self::Tail(secs) => Self::Tail((secs as f64 * factor) as usize),
+            Self::Full => Self::Full,
+            Self::Span { start, secs } => Self::Span {
+                start: (start as f64 * factor) as usize,
+                secs: (secs as f64 * factor) as usize,
+            },
+        }
+    }
+}
+
+/// A [`Window`] along with a label naming the region of the file it covers, such as `"opening"`
+/// or `"bumper"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedWindow {
+    pub label: String,
+    pub window: Window,
+}
+
+impl NamedWindow {
+    pub fn new<T: ToString>(label: T, window: Window) -> Self {
+        Self {
+            label: label.to_string(),
+            window,
+        }
+    }
+}
+
+/// Picks a chapter of a file, see [`SectionizerConfig::chapter`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChapterSelector {
+    /// The chapter at this index, starting at `0`.
+    Index(usize),
+    /// The first chapter whose title matches, ignoring case, such as `"Opening"`.
+    Title(String),
+}
+
+/// How the maximum distance for two frames to match is compared against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistanceBound {
+    /// Frames match if their distance is at most the maximum distance.
+    Inclusive,
+    /// Frames match if their distance is below the maximum distance, a maximum of `0` never
+    /// matches.
+    Exclusive,
+}
+
+/// Measure of how similar two frames are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Similarity {
+    /// Hamming distance between the perceptual hashes of the frames.
+    Hash,
+    /// L1 distance between coarse color histograms of the frames. This is robust to the spatial
+    /// artifacts of heavy re-encodes but ignores the layout of the frame, and its distances run
+    /// higher than hash distances, up to `384`.
+    Histogram,
+    /// Hash distance plus the histogram distance scaled by `histogram_weight`.
+    Combined { histogram_weight: f64 },
+}
+
+/// Rounding applied when converting a timestamp to whole seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Floor,
+    Round,
+    Ceil,
+}
+
+impl Rounding {
+    pub fn apply(&self, secs: f64) -> u128 {
+        match self {
+            Self::Floor => secs.floor() as u128,
+            Self::Round => secs.round() as u128,
+            Self::Ceil => secs.ceil() as u128,
+        }
+    }
+}
+
+/// Policy picking a single section out of several candidates, such as a recap and an opening
+/// both close to the start of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionPreference {
+    /// The longest section, the earliest one on ties.
+    Longest,
+    /// The section with the highest confidence, then the longest, then the earliest.
+    HighestConfidence,
+    /// The earliest section, the longest one on ties.
+    Earliest,
+}
+
+impl SectionPreference {
+    /// Returns the preferred of `candidates`, which are sections along with their confidence.
+    pub fn pick(
+        &self,
+        candidates: impl IntoIterator<Item = ((u128, u128), f64)>,
+    ) -> Option<(u128, u128)> {
+        let len = |(start, end): (u128, u128)| end - start;
+
+        candidates
+            .into_iter()
+            .max_by(|(a, a_conf), (b, b_conf)| {
+                let (longer, earlier) = (len(*a).cmp(&len(*b)), b.0.cmp(&a.0));
+
+                match self {
+                    Self::Longest => longer.then(earlier),
+                    Self::HighestConfidence => a_conf
+                        .partial_cmp(b_conf)
+                        .unwrap_or(Ordering::Equal)
+                        .then(longer)
+                        .then(earlier),
+                    Self::Earliest => earlier.then(longer),
+                }
+            })
+            .map(|(x, _)| x)
+    }
+}
+
+/// How the confidence of a section is scored, both scaled down the closer the median distance of
+/// the matches gets to the threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionScore {
+    /// Fraction of the frames of the section which matched.
+    Density,
+    /// Fraction of the frames of the section covered by its longest run of consecutive matched
+    /// frames, which favours a section matching unbroken over one made of scattered matches.
+    RunLength,
+}
+
+/// Order the matched frames of a window are walked in to find sections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanDirection {
+    /// Every section of the window is reported.
+    Forward,
+    /// Frames are walked from the end of the window back, and only the first run of matches met
+    /// is reported, starting where that run first appears counting backward. Along with a
+    /// `reverse` window, this is the recurring end credits rather than anything else the tail
+    /// happens to share, such as a preview of the next episode.
+    Backward,
+}
+
+/// When to deinterlace the frames of a file before hashing them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Deinterlace {
+    /// Frames are hashed as decoded.
+    Off,
+    /// Every file is deinterlaced with the given filter.
+    Always(DeinterlaceFilter),
+    /// Files whose video stream ffprobe reports as interlaced, through its field order, are
+    /// deinterlaced with the given filter.
+    Auto(DeinterlaceFilter),
+}
+
+/// When to tonemap the frames of a file to SDR before hashing them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemap {
+    /// Frames are converted to `rgb24` as decoded.
+    Off,
+    /// Every file is tonemapped, this also compresses the highlights of SDR sources.
+    Always,
+    /// Files whose video stream ffprobe reports with a PQ or HLG transfer are tonemapped.
+    Auto,
+}
+
+/// Channels which audio is mixed down from before it is fingerprinted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioMix {
+    /// Every channel is mixed down to mono.
+    Mono,
+    /// Audio is mixed down to stereo and only the difference between both channels is kept.
+    /// Dialogue is mostly mixed to the center, including the center channel of surround tracks,
+    /// and cancels out while music is spread wider, so dubs still match the original on the
+    /// music of an opening. Mono tracks cancel out entirely and yield no fingerprints.
+    Side,
+}
+
+/// ffmpeg filter used to deinterlace frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeinterlaceFilter {
+    Yadif,
+    /// Slower than `Yadif` but leaves fewer artifacts around motion.
+    Bwdif,
+}
+
+impl DeinterlaceFilter {
+    /// Returns the name of the filter in an ffmpeg filter graph.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Yadif => "yadif",
+            Self::Bwdif => "bwdif",
+        }
+    }
+}
+
+/// Granularity at which two files are matched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Granularity {
+    /// Every frame is hashed and matched on its own.
+    Frame,
+    /// Frames are aggregated into one hash per second which is then matched. This is more robust
+    /// to small per-frame differences between encodes but yields coarser sections.
+    Scene,
+}
+
+/// Overrides for a single [`Sectionizer::categorize_with`](crate::Sectionizer::categorize_with)
+/// call. Fields left as `None` fall back to the [`SectionizerConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct CategorizeOptions {
+    /// Frame rate of the first file, use this when its container metadata is wrong. This takes
+    /// precedence over both `fps` and the rate probed through `detect_fps`.
+    pub fps1: Option<f64>,
+    /// Frame rate of the second file.
+    pub fps2: Option<f64>,
+    /// Algorithm used to hash frames, overriding `hash_alg`.
+    pub hash_alg: Option<img_hash::HashAlg>,
+    /// Maximum hash distance for two frames to match, overriding `hash_max_dist`. This also takes
+    /// precedence over `auto_threshold` and `threshold_sweep`.
+    pub hash_max_dist: Option<isize>,
+    /// Window analyzed in the first file, overriding the head or tail window. Together with
+    /// `window2` this lines up the openings of files which have them at different positions, for
+    /// example after a longer cold open. Ignored by `categorize_windows`.
+    pub window1: Option<Window>,
+    /// Window analyzed in the second file. [`Window::Full`] here matches a short reference clip in
+    /// its entirety against the sampled window of the first file, see
+    /// [`Sectionizer::find_reference`](crate::Sectionizer::find_reference).
+    pub window2: Option<Window>,
+}
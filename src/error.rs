@@ -5,4 +5,27 @@ use err_derive::Error;
 pub enum SectionizerError {
     #[error(display = "An Error has occured with nightfall")]
     NightfallError(#[error(source)] nightfall::error::NightfallError),
+    #[error(display = "An IO error has occured")]
+    IoError(#[error(source)] std::io::Error),
+    #[error(display = "Failed to probe metadata for {}", _0)]
+    ProbeFailed(String),
+    #[error(display = "Failed to (de)serialize data")]
+    JsonError(#[error(source)] serde_json::Error),
+    #[error(display = "ffmpeg produced no frames for {}: {}", _0, _1)]
+    ExtractionFailed(String, String),
+    #[error(display = "File not found: {}", _0)]
+    FileNotFound(String),
+    #[error(display = "This Sectionizer was created without ffmpeg")]
+    NoFfmpeg,
+    #[error(
+        display = "ffmpeg profile {} emits frames of {} bytes, expected {}",
+        _0,
+        _1,
+        _2
+    )]
+    FrameSizeMismatch(String, usize, usize),
+    #[error(display = "ffmpeg profile {} emits {} frames, expected rgb24", _0, _1)]
+    PixelFormatMismatch(String, String),
+    #[error(display = "Failed to read image {}: {}", _0, _1)]
+    ImageFailed(String, String),
 }
@@ -0,0 +1,76 @@
+//! Helpers for measuring detection accuracy against hand-labeled sections.
+use crate::Sections;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Accuracy of a set of detected sections compared to the ground truth.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Intersection over union of the detected and ground-truth time ranges.
+    pub iou: f64,
+    /// Fraction of the detected time which lies within the ground truth.
+    pub precision: f64,
+    /// Fraction of the ground-truth time which was detected.
+    pub recall: f64,
+}
+
+/// Method `evaluate` compares `detected` against the ground truth sections `truth`.
+///
+/// Both are treated as half-open `[start, end)` ranges in seconds from the start of the file, as
+/// [`Sections::sections`] are, the detected sections are therefore converted to absolute time
+/// first. Overlapping ranges within
+/// either set are merged before comparing. A metric whose denominator is empty (ie. nothing was
+/// detected, or there is nothing to detect) is reported as `1.0`.
+pub fn evaluate(detected: &Sections, truth: &[(u128, u128)]) -> EvalReport {
+    let detected = merge(detected.absolute());
+    let truth = merge(truth.to_vec());
+
+    let detected_len = total_len(&detected);
+    let truth_len = total_len(&truth);
+
+    let intersection = detected
+        .iter()
+        .flat_map(|a| truth.iter().map(move |b| (a, b)))
+        .map(|((a_start, a_end), (b_start, b_end))| {
+            a_end.min(b_end).saturating_sub(*a_start.max(b_start))
+        })
+        .sum::<u128>();
+
+    let union = detected_len + truth_len - intersection;
+
+    EvalReport {
+        iou: ratio(intersection, union),
+        precision: ratio(intersection, detected_len),
+        recall: ratio(intersection, truth_len),
+    }
+}
+
+fn ratio(num: u128, denom: u128) -> f64 {
+    if denom == 0 {
+        return 1.0;
+    }
+
+    num as f64 / denom as f64
+}
+
+fn total_len(ranges: &[(u128, u128)]) -> u128 {
+    ranges
+        .iter()
+        .map(|(start, end)| end.saturating_sub(*start))
+        .sum()
+}
+
+pub(crate) fn merge(mut ranges: Vec<(u128, u128)>) -> Vec<(u128, u128)> {
+    ranges.sort_unstable();
+
+    let mut merged: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+}
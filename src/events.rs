@@ -0,0 +1,33 @@
+//! Events emitted while running [`Sectionizer::categorize_events`](crate::Sectionizer::categorize_events)
+//! and [`Sectionizer::categorize_callback`](crate::Sectionizer::categorize_callback).
+use crate::Sections;
+
+/// A section of the first file reported by
+/// [`Sectionizer::categorize_callback`](crate::Sectionizer::categorize_callback) as soon as it
+/// can no longer grow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectedSection {
+    /// The section in seconds, relative to the analyzed window.
+    pub section: (u128, u128),
+    /// Fraction between `0.0` and `1.0` of the frames of the section which matched. Sections
+    /// left to be reported once matching finished carry their
+    /// [`Quality::section_confidence`](crate::Quality::section_confidence) instead.
+    pub confidence: f64,
+}
+
+/// A single step of a `categorize` run, sent over a channel so that a GUI or other observer can
+/// follow along from another task.
+#[derive(Clone, Debug)]
+pub enum SectionizerEvent {
+    /// Extraction has started for both files.
+    Started { file1: String, file2: String },
+    /// `frames` frames have been decoded so far for `file`.
+    Progress { file: String, frames: u64 },
+    /// All frames of `file` have been decoded.
+    FileDone { file: String, frames: u64 },
+    /// Matching finished, these are the same sections `categorize_events` returns. They are boxed
+    /// to keep the other events small.
+    SectionsReady(Box<(Sections, Sections)>),
+    /// The run failed with the given error.
+    Error(String),
+}
@@ -0,0 +1,172 @@
+//! Conversions of [`Sections`](crate::Sections) into the shapes consumed by other services.
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::Window;
+use crate::Result;
+use crate::Sections;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// What a [`Marker`] skips over.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerKind {
+    Intro,
+    Credits,
+}
+
+/// A skippable range of a media file, in seconds from its start.
+///
+/// nightfall has no marker type of its own, so this is the JSON shape handed to the media server
+/// for intro/credits skipping:
+///
+/// ```json
+/// { "kind": "intro", "start": 62, "end": 151 }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub start: u128,
+    pub end: u128,
+}
+
+impl Sections {
+    /// Converts every section into a [`Marker`] of `kind`, in absolute time.
+    pub fn to_markers(&self, kind: MarkerKind) -> Vec<Marker> {
+        self.absolute()
+            .into_iter()
+            .map(|(start, end)| Marker { kind, start, end })
+            .collect()
+    }
+
+    /// Appends the sections as a single line of JSON to the file at `path`, creating it if it
+    /// doesn't exist yet. Every line carries the `target`, `window` and `window_start` of its
+    /// sections, so a batch run calling this as each file completes leaves a durable record of
+    /// which files are done.
+    pub fn append_jsonl<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+
+        // a single write keeps lines whole even if several runs append to the same file.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&line)?;
+
+        Ok(())
+    }
+
+    /// Converts the sections into a [`SectionsDto`] of `kind`, in absolute time.
+    pub fn to_dto(&self, kind: MarkerKind) -> SectionsDto {
+        let sections = self
+            .with_confidence()
+            .into_iter()
+            .map(|((start, end), confidence)| SectionDto {
+                start_ms: start * 1000,
+                end_ms: end * 1000,
+                kind,
+                confidence,
+            })
+            .collect();
+
+        SectionsDto {
+            version: DTO_VERSION,
+            file: self.target.clone(),
+            fps: self.fps,
+            sections,
+        }
+    }
+
+    /// Converts the sections into a [`SectionMask`] covering the analyzed window. The end of every
+    /// section is exclusive, so its bit is left unset.
+    pub fn to_mask(&self) -> SectionMask {
+        let analyzed = match self.window {
+            Some(Window::Head(secs) | Window::Tail(secs) | Window::Span { secs, .. }) => {
+                secs as u128
+            }
+            Some(Window::Full) | None => (self.analyzed as f64 / self.fps).ceil() as u128,
+        };
+        let len = self
+            .sections
+            .iter()
+            .map(|(_, end)| *end)
+            .fold(analyzed, u128::max) as usize;
+
+        let mut bits = vec![0u8; len.div_ceil(8)];
+        for (start, end) in self.sections.iter() {
+            for second in *start as usize..*end as usize {
+                bits[second / 8] |= 1 << (second % 8);
+            }
+        }
+
+        SectionMask {
+            offset: self.window_start,
+            fps: self.fps,
+            len,
+            bits,
+        }
+    }
+}
+
+/// Version of the [`SectionsDto`] format, bumped on every incompatible change to it.
+pub const DTO_VERSION: u32 = 1;
+
+/// Flat form of [`Sections`] meant to be served as is over HTTP:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "file": "episode.mkv",
+///   "fps": 23.976,
+///   "sections": [{ "start_ms": 62000, "end_ms": 151000, "kind": "intro", "confidence": 0.9 }]
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SectionsDto {
+    /// Always [`DTO_VERSION`] for DTOs built by this version of the crate.
+    pub version: u32,
+    pub file: String,
+    pub fps: f64,
+    pub sections: Vec<SectionDto>,
+}
+
+/// A single section of a [`SectionsDto`], in milliseconds from the start of the file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SectionDto {
+    pub start_ms: u128,
+    pub end_ms: u128,
+    pub kind: MarkerKind,
+    /// Between `0.0` and `1.0`, see
+    /// [`Quality::section_confidence`](crate::Quality::section_confidence).
+    pub confidence: f64,
+}
+
+/// Compact form of [`Sections`], with one bit per analyzed second set if that second lies within
+/// a section. Bit `i` is bit `i % 8` of byte `i / 8`, counting from the least significant bit, and
+/// stands for second `offset + i` of the file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SectionMask {
+    /// Second of the file the first bit stands for.
+    pub offset: u128,
+    /// Frame rate the frames were mapped to seconds with.
+    pub fps: f64,
+    /// Number of seconds covered by `bits`.
+    pub len: usize,
+    pub bits: Vec<u8>,
+}
+
+impl SectionMask {
+    /// Returns whether second `second` of the file lies within a section.
+    pub fn contains(&self, second: u128) -> bool {
+        let bit = match second.checked_sub(self.offset) {
+            Some(x) if x < self.len as u128 => x as usize,
+            _ => return false,
+        };
+
+        self.bits[bit / 8] >> (bit % 8) & 1 == 1
+    }
+}
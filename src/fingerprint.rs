@@ -0,0 +1,74 @@
+//! Persistent fingerprints of known sections, such as a show's opening, which new files can be
+//! matched against with [`Sectionizer::find_template`](crate::Sectionizer::find_template) instead
+//! of being compared pairwise.
+use crate::Frame;
+use crate::Result;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::path::PathBuf;
+
+/// The hashed frames of a confirmed section along with the settings they were extracted with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Free-form label, such as `"opening"`.
+    pub label: String,
+    pub frames: Vec<Frame>,
+    /// Frame rate of the file the fingerprint was taken from.
+    pub fps: f64,
+    /// Whether the fingerprint was taken from the tail of the file.
+    pub reverse: bool,
+    /// Hash distance threshold to use when matching against this fingerprint.
+    pub hash_max_dist: isize,
+}
+
+impl Fingerprint {
+    /// Returns the length of the fingerprinted section in seconds.
+    pub fn secs(&self) -> f64 {
+        let idx = self.frames.iter().map(|x| x.idx);
+        match (idx.clone().min(), idx.max()) {
+            (Some(first), Some(last)) => (last - first) as f64 / self.fps,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Storage for fingerprints keyed by an arbitrary id, typically a show id.
+pub trait FingerprintStore {
+    fn save(&self, id: &str, fingerprint: &Fingerprint) -> Result<()>;
+    /// Returns `None` if no fingerprint is stored under `id`.
+    fn load(&self, id: &str) -> Result<Option<Fingerprint>>;
+}
+
+/// A [`FingerprintStore`] keeping one JSON file per id in a directory.
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+impl FingerprintStore for DirStore {
+    fn save(&self, id: &str, fingerprint: &Fingerprint) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(id), serde_json::to_vec(fingerprint)?)?;
+
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<Fingerprint>> {
+        match std::fs::read(self.path(id)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Helpers for cutting frame vectors down to a range, to feed the frame level APIs such as
+//! [`Sectionizer::categorize_frames`](crate::Sectionizer::categorize_frames) with controlled
+//! inputs, for example a file with its known intro taken out.
+//!
+//! Frames keep their index, so sections found in a slice are still timed like in the file the
+//! frames were extracted from. All of these expect `frames` sorted by index, as extracted.
+use std::ops::Range;
+
+use crate::time::secs_to_frame;
+use crate::Frame;
+
+/// Returns the frames of `frames` whose index lies within `range`.
+pub fn slice(frames: &[Frame], range: Range<u64>) -> Vec<Frame> {
+    let start = frames.partition_point(|x| x.idx() < range.start);
+    let end = frames.partition_point(|x| x.idx() < range.end);
+
+    frames[start..end.max(start)].to_vec()
+}
+
+/// Returns the frames of `frames` shown within `range`, in seconds.
+pub fn slice_secs(frames: &[Frame], range: Range<f64>, fps: f64) -> Vec<Frame> {
+    slice(frames, secs_range(range, fps))
+}
+
+/// Returns the frames of `frames` whose index lies outside of `range`.
+pub fn exclude(frames: &[Frame], range: Range<u64>) -> Vec<Frame> {
+    frames
+        .iter()
+        .filter(|x| !range.contains(&x.idx()))
+        .copied()
+        .collect()
+}
+
+/// Returns the frames of `frames` shown outside of `range`, in seconds.
+pub fn exclude_secs(frames: &[Frame], range: Range<f64>, fps: f64) -> Vec<Frame> {
+    exclude(frames, secs_range(range, fps))
+}
+
+fn secs_range(range: Range<f64>, fps: f64) -> Range<u64> {
+    secs_to_frame(range.start, fps)..secs_to_frame(range.end, fps)
+}
@@ -0,0 +1,158 @@
+//! Frame hashes of varying bit widths.
+
+use std::fmt;
+
+use image::RgbImage;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Distance reported between two hashes of different widths. This is far above any sensible
+/// threshold so such hashes never match, while staying small enough for the arithmetic done by
+/// the BkTree not to overflow.
+const MISMATCHED_WIDTH_DIST: isize = 1 << 16;
+
+/// Width of the hashes computed for every frame.
+///
+/// Wider hashes are computed from a larger thumbnail of the frame, so they tell similar frames
+/// apart better at the cost of memory and matching time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashWidth {
+    Bits64,
+    Bits128,
+    Bits256,
+}
+
+impl HashWidth {
+    /// Number of bits a hash of this width can hold.
+    pub fn bits(&self) -> u32 {
+        match self {
+            Self::Bits64 => 64,
+            Self::Bits128 => 128,
+            Self::Bits256 => 256,
+        }
+    }
+
+    /// Size of the thumbnail `img_hash` hashes for this width, as large as the width can hold.
+    /// Every size is already a multiple of 4 on both axes, so `img_hash` doesn't round it up past
+    /// the width for `DoubleGradient` or `Blockhash`. `DoubleGradient` only fills part of the width,
+    /// as it compares the pixels of a thumbnail half that size in both directions.
+    pub(crate) fn hash_size(&self) -> (u32, u32) {
+        match self {
+            Self::Bits64 => (8, 8),
+            Self::Bits128 => (16, 8),
+            Self::Bits256 => (16, 16),
+        }
+    }
+}
+
+/// Computes the hash of a frame, in place of the `img_hash` hashers the crate uses by default.
+///
+/// Frames are matched on the distance between their hashes, so an implementation should map
+/// similar frames to hashes differing in few bits, always of the same width.
+pub trait FrameHasher: fmt::Debug + Send + Sync {
+    fn hash(&self, img: &RgbImage) -> FrameHash;
+}
+
+/// Hash of a single frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FrameHash {
+    Bits64(u64),
+    Bits128(u128),
+    Bits256([u64; 4]),
+}
+
+impl FrameHash {
+    /// Builds a hash of `width` out of the big endian `bytes` produced by `img_hash`. Bytes past
+    /// the width are ignored and missing ones are taken to be zero.
+    pub(crate) fn from_bytes(width: HashWidth, bytes: &[u8]) -> Self {
+        let mut padded = [0u8; 32];
+        let len = bytes.len().min(width.bits() as usize / 8);
+        padded[..len].copy_from_slice(&bytes[..len]);
+
+        let word = |i: usize| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(&padded[i * 8..(i + 1) * 8]);
+            u64::from_be_bytes(word)
+        };
+
+        match width {
+            HashWidth::Bits64 => Self::Bits64(word(0)),
+            HashWidth::Bits128 => Self::Bits128((word(0) as u128) << 64 | word(1) as u128),
+            HashWidth::Bits256 => Self::Bits256([word(0), word(1), word(2), word(3)]),
+        }
+    }
+
+    /// Builds a hash of `width` with only the bits yielded by `bits` set.
+    pub(crate) fn from_bits(width: HashWidth, bits: impl Iterator<Item = u32>) -> Self {
+        let mut hash = Self::zero(width);
+
+        for bit in bits {
+            match &mut hash {
+                Self::Bits64(x) => *x |= 1 << bit,
+                Self::Bits128(x) => *x |= 1 << bit,
+                Self::Bits256(x) => x[(bit / 64) as usize] |= 1 << (bit % 64),
+            }
+        }
+
+        hash
+    }
+
+    fn zero(width: HashWidth) -> Self {
+        match width {
+            HashWidth::Bits64 => Self::Bits64(0),
+            HashWidth::Bits128 => Self::Bits128(0),
+            HashWidth::Bits256 => Self::Bits256([0; 4]),
+        }
+    }
+
+    pub fn width(&self) -> HashWidth {
+        match self {
+            Self::Bits64(_) => HashWidth::Bits64,
+            Self::Bits128(_) => HashWidth::Bits128,
+            Self::Bits256(_) => HashWidth::Bits256,
+        }
+    }
+
+    /// Returns whether `bit` is set.
+    pub fn bit(&self, bit: u32) -> bool {
+        match self {
+            Self::Bits64(x) => x >> bit & 1 == 1,
+            Self::Bits128(x) => x >> bit & 1 == 1,
+            Self::Bits256(x) => x[(bit / 64) as usize] >> (bit % 64) & 1 == 1,
+        }
+    }
+
+    /// Returns the number of bits set.
+    pub fn ones(&self) -> u32 {
+        match self {
+            Self::Bits64(x) => x.count_ones(),
+            Self::Bits128(x) => x.count_ones(),
+            Self::Bits256(x) => x.iter().map(|x| x.count_ones()).sum(),
+        }
+    }
+
+    /// Returns the number of bits which differ between `self` and `other`. Hashes of different
+    /// widths are never considered close.
+    pub fn distance(&self, other: &Self) -> isize {
+        match (self, other) {
+            (Self::Bits64(a), Self::Bits64(b)) => (a ^ b).count_ones() as isize,
+            (Self::Bits128(a), Self::Bits128(b)) => (a ^ b).count_ones() as isize,
+            (Self::Bits256(a), Self::Bits256(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| (a ^ b).count_ones() as isize)
+                .sum(),
+            _ => MISMATCHED_WIDTH_DIST,
+        }
+    }
+}
+
+impl fmt::Display for FrameHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bits64(x) => write!(f, "{:016x}", x),
+            Self::Bits128(x) => write!(f, "{:032x}", x),
+            Self::Bits256(x) => x.iter().try_for_each(|x| write!(f, "{:016x}", x)),
+        }
+    }
+}
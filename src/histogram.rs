@@ -0,0 +1,70 @@
+//! Coarse color histograms, a similarity signal complementary to the perceptual hash.
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Number of bins per color channel.
+const BINS: usize = 4;
+/// Value the bins of a single channel add up to.
+const CHANNEL_TOTAL: u32 = 64;
+
+/// Color histogram of a frame, with `BINS` bins for each of the red, green and blue channels.
+///
+/// Unlike the perceptual hash this ignores where colors are in the frame, which makes it robust
+/// to the blocking and ringing heavy re-encodes introduce, but blind to motion within a shot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Histogram([u8; BINS * 3]);
+
+impl Histogram {
+    /// Computes the histogram of `frame`. The bins of every channel are normalized to add up to
+    /// `CHANNEL_TOTAL` so that histograms of differently sized frames compare.
+    pub fn new(frame: &image::RgbImage) -> Self {
+        let mut counts = [0u32; BINS * 3];
+
+        for px in frame.pixels() {
+            for (channel, value) in px.0.iter().enumerate() {
+                counts[channel * BINS + *value as usize * BINS / 256] += 1;
+            }
+        }
+
+        let pixels = (frame.width() * frame.height()).max(1);
+        let mut bins = [0u8; BINS * 3];
+        for (bin, count) in bins.iter_mut().zip(counts.iter()) {
+            *bin = ((count * CHANNEL_TOTAL + pixels / 2) / pixels) as u8;
+        }
+
+        Self(bins)
+    }
+
+    /// Returns the per-bin average of `histograms`, `None` if there are none.
+    pub fn average<'a>(histograms: impl Iterator<Item = &'a Histogram>) -> Option<Self> {
+        let mut sums = [0u32; BINS * 3];
+        let mut len = 0;
+
+        for histogram in histograms {
+            for (sum, bin) in sums.iter_mut().zip(histogram.0.iter()) {
+                *sum += *bin as u32;
+            }
+            len += 1;
+        }
+
+        if len == 0 {
+            return None;
+        }
+
+        let mut bins = [0u8; BINS * 3];
+        for (bin, sum) in bins.iter_mut().zip(sums.iter()) {
+            *bin = ((sum + len / 2) / len) as u8;
+        }
+
+        Some(Self(bins))
+    }
+
+    /// Returns the L1 distance between `self` and `other`, between `0` and `6 * CHANNEL_TOTAL`.
+    pub fn distance(&self, other: &Self) -> isize {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (*a as isize - *b as isize).abs())
+            .sum()
+    }
+}
@@ -0,0 +1,83 @@
+//! A summary of the environment a [`Sectionizer`](crate::Sectionizer) runs in, for health checks
+//! and bug reports.
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::config::SectionizerConfig;
+use crate::config::Window;
+use crate::hash::HashWidth;
+
+/// Returned by [`Sectionizer::info`](crate::Sectionizer::info).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SectionizerInfo {
+    /// Version of this crate.
+    pub version: String,
+    /// Path of the configured `ffmpeg` binary.
+    pub ffmpeg: String,
+    /// First line of `ffmpeg -version`, `None` if it couldn't be run.
+    pub ffmpeg_version: Option<String>,
+    /// Path of the configured `ffprobe` binary.
+    pub ffprobe: String,
+    pub config: ConfigSummary,
+}
+
+/// The settings of a [`SectionizerConfig`] which shape results the most. Settings which aren't
+/// plain values are in their debug representation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConfigSummary {
+    pub hash_max_dist: isize,
+    pub auto_threshold: bool,
+    pub hash_width: HashWidth,
+    pub frame_size: (u32, u32),
+    pub hash_stride: u64,
+    pub granularity: String,
+    pub similarity: String,
+    pub head: Window,
+    pub tail: Window,
+    pub bucket_secs: f64,
+    pub merge_gap_secs: f64,
+    pub min_total_matches: usize,
+    pub early_stop: bool,
+    /// Names of the steps of the post-processing pipeline, in order.
+    pub post_processors: Vec<String>,
+}
+
+impl ConfigSummary {
+    pub fn new(config: &SectionizerConfig) -> Self {
+        Self {
+            hash_max_dist: config.hash_max_dist,
+            auto_threshold: config.auto_threshold,
+            hash_width: config.hash_width,
+            frame_size: config.frame_size,
+            hash_stride: config.hash_stride,
+            granularity: format!("{:?}", config.granularity),
+            similarity: format!("{:?}", config.similarity),
+            head: config.window(false),
+            tail: config.window(true),
+            bucket_secs: config.bucket_secs,
+            merge_gap_secs: config.merge_gap_secs,
+            min_total_matches: config.min_total_matches,
+            early_stop: config.early_stop.is_some(),
+            post_processors: config
+                .post_processors
+                .iter()
+                .map(|x| format!("{:?}", x))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the first line of `ffmpeg -version`.
+pub(crate) async fn ffmpeg_version(ffmpeg: &str) -> Option<String> {
+    let output = Command::new(ffmpeg).arg("-version").output().await.ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|x| x.trim().to_string())
+}
@@ -1,50 +1,410 @@
 //! Sectionizer
 //! This crate contains various utilities useful for detecting similar scenes between video files. This is mostly useful for detecting credits, openings, endings and so on.
-//! At the moment only video streams are compared but in the future audio analysis will also be added to augument detection and make it more accurate.
+//! Inputs can be local paths or any URL ffmpeg can open, such as `https://` links, which are passed
+//! to ffmpeg unchanged.
+//! Video streams are compared, inputs without any video are matched on their audio instead, see
+//! [`audio`].
 #![feature(box_syntax, slice_group_by)]
 
+pub mod audio;
+pub mod cache;
+pub mod checkpoint;
+pub mod config;
 pub mod error;
+pub mod eval;
+pub mod events;
+pub mod export;
+pub mod fingerprint;
+pub mod frames;
+pub mod hash;
+pub mod histogram;
+pub mod info;
+pub mod motion;
+pub mod postprocess;
+mod probe;
+pub mod season;
+pub mod stats;
+pub mod time;
 
+use nightfall::profile::Profile;
 use nightfall::profile::RawVideoProfile;
 use nightfall::profile::StreamType;
 use nightfall::*;
 
 use futures::join;
+use tokio::io::AsyncRead;
 use tokio::io::AsyncReadExt;
+use tokio::io::BufReader;
 use tokio::process::ChildStdout;
+use tokio::sync::mpsc;
 
+use bktree::BkTree;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::ops::RangeInclusive;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
 
-use bktree::BkTree;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::cache::CacheKey;
+use crate::cache::CachedFrames;
+use crate::checkpoint::Checkpoint;
+use crate::config::CategorizeOptions;
+use crate::config::ChapterSelector;
+use crate::config::Deinterlace;
+use crate::config::DeinterlaceFilter;
+use crate::config::DistanceBound;
+use crate::config::EarlyStop;
+use crate::config::Granularity;
+use crate::config::NamedWindow;
+use crate::config::SectionPreference;
+use crate::config::SectionScore;
+use crate::config::SectionizerConfig;
+use crate::config::Similarity;
+use crate::config::Tonemap;
+use crate::config::Window;
+use crate::error::SectionizerError;
+use crate::events::DetectedSection;
+use crate::events::SectionizerEvent;
+use crate::export::Marker;
+use crate::export::MarkerKind;
+use crate::fingerprint::Fingerprint;
+use crate::fingerprint::FingerprintStore;
+use crate::hash::FrameHash;
+use crate::hash::HashWidth;
+use crate::histogram::Histogram;
+use crate::info::ConfigSummary;
+use crate::info::SectionizerInfo;
+use crate::postprocess::Bucket;
+use crate::postprocess::Ctx;
+use crate::postprocess::Section;
+use crate::stats::Stats;
+use crate::time::frame_to_secs;
 
 const IMG_H: usize = 16;
 const IMG_W: usize = 18;
 const IMG_SIZE: usize = IMG_H * IMG_W * 3;
 const HASHER: img_hash::HashAlg = img_hash::HashAlg::DoubleGradient;
 const HASH_MAX_DIST: isize = 2;
+/// Largest distance considered when building the distance histogram used by `auto_threshold`.
+const AUTO_THRESHOLD_CEIL: isize = 16;
+/// Number of decoded frames between two progress events.
+const PROGRESS_INTERVAL: u64 = 240;
+/// Hash distance between two consecutive frames above which they are considered to be a scene cut.
+const SCENE_CUT_DIST: isize = 12;
+/// Frames whose hash has at most this many bits set are flat, such as black frames, as the
+/// gradients of a flat frame are all zero.
+const FLAT_HASH_MAX_ONES: u32 = 2;
+/// Minimum fraction of the matched frames of a section which have to be matched with frames in a
+/// section of the other file for `cross_validate` to keep it.
+const CROSS_VALIDATION_MIN_RATIO: f64 = 0.5;
+/// `hash_stride` used for [`Window::Full`] when none is configured.
+pub const FULL_FILE_HASH_STRIDE: u64 = 4;
+/// Coverage below which [`Sections::is_partial`] considers a section a partial match of its
+/// reference, such as a shortened opening.
+pub const PARTIAL_MAX_COVERAGE: f64 = 0.9;
+/// Directory within `cache_dir` the frame cache of `categorize_cached` is kept in.
+const FRAME_CACHE_DIR: &str = "frames";
+/// Filters tonemapping HDR frames to 8-bit SDR `rgb24`, inserted ahead of the scaling for
+/// `tonemap`. The frames are linearized and converted to BT.709 primaries before `tonemap`
+/// compresses their highlights, then brought back to the BT.709 transfer. There is no HDR sample
+/// in the tree to test this against. To check a file by hand, write the frames of its HDR and SDR
+/// releases to `debug_frames_dir`, the hashes in their names should be within `hash_max_dist`.
+const TONEMAP_FILTERS: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+    tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=rgb24";
 
 pub type Result<T> = ::core::result::Result<T, crate::error::SectionizerError>;
 
 /// `0` Frame Hash, `1` frame idx
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Frame {
-    hash: u128,
+    hash: FrameHash,
     idx: u64,
+    /// Only computed when the configured `similarity` needs it.
+    #[serde(default)]
+    histogram: Option<Histogram>,
+    /// Number of identical frames following this one which it stands for in a tree, see
+    /// `dedupe_tree_frames`.
+    #[serde(default)]
+    run: u64,
+}
+
+impl Frame {
+    pub fn new(hash: FrameHash, idx: u64) -> Self {
+        Self {
+            hash,
+            idx,
+            histogram: None,
+            run: 0,
+        }
+    }
+
+    /// Returns the frame with its color histogram attached.
+    pub fn with_histogram(self, histogram: Histogram) -> Self {
+        Self {
+            histogram: Some(histogram),
+            ..self
+        }
+    }
+
+    pub fn hash(&self) -> FrameHash {
+        self.hash
+    }
+
+    pub fn histogram(&self) -> Option<Histogram> {
+        self.histogram
+    }
+
+    /// Index of the frame within the analyzed window.
+    pub fn idx(&self) -> u64 {
+        self.idx
+    }
+
+    /// Index of the last frame this one stands for, past `idx` when the frame was collapsed with
+    /// the identical frames following it by `dedupe_tree_frames`.
+    pub fn last_idx(&self) -> u64 {
+        self.idx + self.run
+    }
+
+    /// Returns the index of the frame this one stands for which is closest to `idx` while at
+    /// least `min_separation` away from it, if any.
+    fn partner_idx(&self, idx: u64, min_separation: u64) -> Option<u64> {
+        let closest = idx.clamp(self.idx, self.last_idx());
+        if closest.abs_diff(idx) >= min_separation {
+            return Some(closest);
+        }
+
+        // the run straddles `idx`, so the closest frame far enough away is on either side of it.
+        let run = self.idx..=self.last_idx();
+        let before = idx.checked_sub(min_separation).filter(|x| run.contains(x));
+        let after = idx.checked_add(min_separation).filter(|x| run.contains(x));
+
+        before.or(after)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct MatchedFrames(Frame, Frame);
+pub struct MatchedFrames(Frame, Frame, isize);
+
+/// Matched frames of a file grouped into candidate sections by `get_groups`.
+struct Grouping {
+    /// Groups of buckets, every group makes up one section.
+    groups: Vec<Vec<Bucket>>,
+    /// Distance of every matched frame to its match, by frame index.
+    distances: BTreeMap<u64, isize>,
+    /// Number of groups dropped for having less than `min_total_matches` matches.
+    rejected: usize,
+    /// Index of the frame every matched frame was matched with.
+    partners: BTreeMap<u64, u64>,
+    /// Every bucket which received a match, only filled in with `debug_groups`.
+    buckets: Vec<BucketInfo>,
+}
+
+/// Sections of a file found by `detect`, before they are converted to seconds.
+struct Detection {
+    sections: Vec<Section>,
+    /// Frames the sections were found in.
+    frames: Vec<Frame>,
+    distances: BTreeMap<u64, isize>,
+    partners: BTreeMap<u64, u64>,
+    /// Number of candidate sections dropped so far.
+    rejected: usize,
+    params: MatchParams,
+}
+
+/// Parameters controlling how `get_sections` matches frames and groups them into sections.
+#[derive(Clone, Copy, Debug)]
+struct MatchParams {
+    /// Maximum hash distance for two frames to match.
+    max_dist: isize,
+    /// Minimum number of frames between two matching frames, used when a file is matched against
+    /// itself.
+    min_separation: u64,
+    /// Frame rate used to bucket frames into seconds.
+    fps: f64,
+    /// Duration of a bucket in seconds.
+    bucket_secs: f64,
+    /// Minimum number of matched frames for a bucket to be kept.
+    min_bucket_matches: usize,
+    /// Maximum number of buckets between two matched buckets for them to be part of the same
+    /// section.
+    merge_gap: u64,
+}
+
+/// Frames decoded from the analyzed window of a file.
+struct Extraction {
+    frames: Vec<Frame>,
+    ignored: IgnoredFrames,
+    /// Window that was actually analyzed, after any scaling.
+    window: Window,
+}
+
+/// Whoever follows a run as it goes.
+#[derive(Clone, Copy, Default)]
+struct Observers<'a> {
+    events: Option<&'a mpsc::Sender<SectionizerEvent>>,
+    /// Called with every section of the first file as soon as it can no longer grow.
+    on_section: Option<&'a dyn Fn(DetectedSection)>,
+}
+
+/// An ffmpeg process frames are read from.
+enum Session {
+    /// A session of the `StateManager`, by id.
+    Managed(String),
+    /// ffmpeg spawned by the `Sectionizer` itself, for filters nightfall's profiles have no room
+    /// for. It is killed once dropped.
+    Direct(tokio::process::Child),
+}
 
+/// Cloning a `Sectionizer` is cheap, clones share the same underlying `StateManager` actor, so they
+/// can be handed out to concurrent tasks freely.
+#[derive(Clone)]
 pub struct Sectionizer {
-    #[allow(dead_code)]
     logger: slog::Logger,
-    state: StateManager,
+    /// `None` for a `Sectionizer` created with [`without_ffmpeg`](Self::without_ffmpeg).
+    state: Option<StateManager>,
+    config: SectionizerConfig,
+    stats: Arc<Stats>,
 }
 
 impl Sectionizer {
     pub fn new(logger: slog::Logger, state: StateManager) -> Self {
-        Self { logger, state }
+        Self::with_config(logger, state, SectionizerConfig::default())
+    }
+
+    /// Creates a `Sectionizer` along with its own `StateManager`, using the ffmpeg binary and
+    /// cache directory from `config`.
+    pub fn from_config(logger: slog::Logger, config: SectionizerConfig) -> Self {
+        let state = StateManager::new(
+            &mut xtra::spawn::Tokio::Global,
+            config.cache_dir.clone(),
+            config.ffmpeg.clone(),
+            logger.clone(),
+        );
+
+        Self::with_config(logger, state, config)
+    }
+
+    /// Creates a `Sectionizer` like [`from_config`](Self::from_config), logging through the `log`
+    /// facade instead of an explicit slog logger.
+    #[cfg(feature = "log")]
+    pub fn from_config_log(config: SectionizerConfig) -> Self {
+        Self::from_config(log_facade(), config)
+    }
+
+    /// Creates a `Sectionizer` like [`without_ffmpeg`](Self::without_ffmpeg), logging through the
+    /// `log` facade instead of an explicit slog logger.
+    #[cfg(feature = "log")]
+    pub fn without_ffmpeg_log(config: SectionizerConfig) -> Self {
+        Self::without_ffmpeg(log_facade(), config)
+    }
+
+    pub fn with_config(
+        logger: slog::Logger,
+        state: StateManager,
+        config: SectionizerConfig,
+    ) -> Self {
+        Self {
+            logger,
+            state: Some(state),
+            config,
+            stats: Default::default(),
+        }
+    }
+
+    /// Creates a `Sectionizer` which never spawns ffmpeg itself, for environments where spawning
+    /// processes is restricted. Frames have to be fed to it through
+    /// [`categorize_readers`](Self::categorize_readers), every method taking file paths fails with
+    /// [`SectionizerError::NoFfmpeg`].
+    pub fn without_ffmpeg(logger: slog::Logger, config: SectionizerConfig) -> Self {
+        Self {
+            logger,
+            state: None,
+            config,
+            stats: Default::default(),
+        }
+    }
+
+    /// Method `categorize_readers` behaves like [`categorize_with`](Self::categorize_with) but
+    /// reads the frames of both files from `reader1` and `reader2` instead of spawning ffmpeg.
+    ///
+    /// The readers must yield raw frames exactly like nightfall's raw video profile does, ie. the
+    /// output of `ffmpeg -i <file> -vf scale=<w>:<h> -pix_fmt rgb24 -f rawvideo -` where `(w, h)`
+    /// is the configured `frame_size`, for example through a named pipe or an inherited file
+    /// descriptor.
+    ///
+    /// # Returns
+    /// The sections for both readers, relative to their first frame and with an empty `target`.
+    pub async fn categorize_readers<R1, R2>(
+        &self,
+        reader1: R1,
+        reader2: R2,
+        options: CategorizeOptions,
+    ) -> Result<(Sections, Sections)>
+    where
+        R1: AsyncRead + Unpin,
+        R2: AsyncRead + Unpin,
+    {
+        let sectionizer = self.with_overrides(&options);
+        let fps1 = options.fps1.unwrap_or(self.config.fps);
+        let fps2 = options.fps2.unwrap_or(self.config.fps);
+
+        let ((framevec1, ignored1), (framevec2, ignored2)) = join!(
+            sectionizer.compute_frame_vec("reader1", reader1, None, |_| {}, |_| false),
+            sectionizer.compute_frame_vec("reader2", reader2, None, |_| {}, |_| false)
+        );
+
+        for (label, frames, ignored) in [
+            ("reader1", &framevec1, ignored1),
+            ("reader2", &framevec2, ignored2),
+        ] {
+            if frames.len() + ignored.total() == 0 {
+                return Err(SectionizerError::ExtractionFailed(
+                    label.to_string(),
+                    String::new(),
+                ));
+            }
+
+            sectionizer.log_extracted(label, frames.len(), ignored);
+        }
+
+        let (sections1, sections2) =
+            sectionizer.categorize_frames(framevec1, framevec2, fps1, fps2);
+
+        Ok((
+            Sections {
+                ignored: ignored1,
+                ..sections1
+            },
+            Sections {
+                ignored: ignored2,
+                ..sections2
+            },
+        ))
+    }
+
+    /// Method `stats` returns the counters of this `Sectionizer`, which are shared with its clones.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Method `info` describes the environment this `Sectionizer` runs in: the crate version, the
+    /// configured ffmpeg along with its version, and a summary of the configuration. This is meant
+    /// for health checks and bug reports.
+    pub async fn info(&self) -> SectionizerInfo {
+        SectionizerInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            ffmpeg: self.config.ffmpeg.clone(),
+            ffmpeg_version: info::ffmpeg_version(&self.config.ffmpeg).await,
+            ffprobe: self.config.ffprobe.clone(),
+            config: ConfigSummary::new(&self.config),
+        }
     }
 
     /// Method `categorize` attempts to match scenes from `file1` and `file2`, returning the sections which match up.
@@ -60,128 +420,3376 @@ impl Sectionizer {
         file2: T,
         reverse: bool,
     ) -> Result<(Sections, Sections)> {
-        let sseof = if reverse { Some(300) } else { None };
+        self.categorize_with(file1, file2, reverse, CategorizeOptions::default())
+            .await
+    }
 
-        let profile = StreamType::RawVideo {
-            map: 0,
-            profile: RawVideoProfile::RawRgb,
-            tt: Some(300),
-            sseof,
+    /// Method `categorize_with` behaves like [`categorize`](Self::categorize) but lets the caller
+    /// override parts of the configuration for this call only.
+    /// # Arguments
+    /// `file1` - First target file path
+    /// `file2` - Second target file path
+    /// `options` - Overrides applied on top of the `Sectionizer`'s configuration
+    pub async fn categorize_with<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+        reverse: bool,
+        options: CategorizeOptions,
+    ) -> Result<(Sections, Sections)> {
+        let window = self.config.window(reverse || self.config.reverse);
+
+        self.with_overrides(&options)
+            .with_window_defaults(window)
+            .run(
+                file1.to_string(),
+                file2.to_string(),
+                window,
+                options,
+                Observers::default(),
+            )
+            .await
+    }
+
+    /// Method `categorize_map` behaves like [`categorize_with`](Self::categorize_with) but
+    /// returns the sections of both files keyed by their path, as passed in.
+    ///
+    /// Matching a file against itself yields a single entry, holding the sections of `file1`.
+    pub async fn categorize_map<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+        reverse: bool,
+        options: CategorizeOptions,
+    ) -> Result<HashMap<String, Sections>> {
+        let (file1, file2) = (file1.to_string(), file2.to_string());
+        let (sections1, sections2) = self
+            .categorize_with(file1.clone(), file2.clone(), reverse, options)
+            .await?;
+
+        let mut sections = HashMap::new();
+        sections.insert(file2, sections2);
+        sections.insert(file1, sections1);
+
+        Ok(sections)
+    }
+
+    /// Method `categorize_callback` behaves like [`categorize_with`](Self::categorize_with) but
+    /// calls `on_section` with every section of `file1` the moment it is found, for example to
+    /// offer skipping an opening before the rest of the window has been decoded.
+    ///
+    /// The first file is matched against the second one as it is decoded, like with
+    /// `early_stop`, and a section is reported once more than `merge_gap_secs` have been decoded
+    /// past its end. Its bounds may still move slightly with the post-processing that follows.
+    /// Sections which only take shape at the end of the window are reported once matching
+    /// finished. Every section is reported once.
+    pub async fn categorize_callback<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+        reverse: bool,
+        options: CategorizeOptions,
+        on_section: impl Fn(DetectedSection),
+    ) -> Result<(Sections, Sections)> {
+        let reported = RefCell::new(Vec::new());
+        let report = |section: DetectedSection| {
+            reported.borrow_mut().push(section.section);
+            on_section(section);
+        };
+
+        let window = self.config.window(reverse || self.config.reverse);
+        let (sections1, sections2) = self
+            .with_overrides(&options)
+            .with_window_defaults(window)
+            .run(
+                file1.to_string(),
+                file2.to_string(),
+                window,
+                options,
+                Observers {
+                    on_section: Some(&report),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let confidence = sections1.quality.section_confidence.iter();
+        for (section, confidence) in sections1.sections.iter().zip(confidence) {
+            let overlaps = |(start, end): &(u128, u128)| *start < section.1 && section.0 < *end;
+            if !reported.borrow().iter().any(overlaps) {
+                on_section(DetectedSection {
+                    section: *section,
+                    confidence: *confidence,
+                });
+            }
+        }
+
+        Ok((sections1, sections2))
+    }
+
+    /// Method `categorize_windows` matches `file1` and `file2` once for every window of
+    /// `windows`, so that a single pass can pick up for example an opening, a recurring
+    /// mid-episode bumper and the end credits.
+    /// # Arguments
+    /// `file1` - First target file path
+    /// `file2` - Second target file path
+    /// `windows` - Windows to analyze, the same windows are used for both files
+    /// `options` - Overrides applied on top of the `Sectionizer`'s configuration
+    ///
+    /// # Returns
+    /// The sections found in every window, labelled and in the order of `windows`.
+    pub async fn categorize_windows<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+        windows: &[NamedWindow],
+        options: CategorizeOptions,
+    ) -> Result<Vec<RegionSections>> {
+        let (file1, file2) = (file1.to_string(), file2.to_string());
+        let sectionizer = self.with_overrides(&options);
+        let mut regions = Vec::with_capacity(windows.len());
+
+        for window in windows {
+            let (sections1, sections2) = sectionizer
+                .with_window_defaults(window.window)
+                .run(
+                    file1.clone(),
+                    file2.clone(),
+                    window.window,
+                    CategorizeOptions {
+                        window1: None,
+                        window2: None,
+                        ..options.clone()
+                    },
+                    Observers::default(),
+                )
+                .await?;
+
+            regions.push(RegionSections {
+                label: window.label.clone(),
+                sections1,
+                sections2,
+            });
+        }
+
+        Ok(regions)
+    }
+
+    /// Method `categorize_events` behaves like [`categorize_with`](Self::categorize_with) but
+    /// additionally reports its progress as [`SectionizerEvent`]s over `events`. Events are dropped
+    /// if the receiver has gone away or, for progress events, if the channel is full.
+    pub async fn categorize_events<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+        reverse: bool,
+        options: CategorizeOptions,
+        events: mpsc::Sender<SectionizerEvent>,
+    ) -> Result<(Sections, Sections)> {
+        let window = self.config.window(reverse || self.config.reverse);
+        let result = self
+            .with_overrides(&options)
+            .with_window_defaults(window)
+            .run(
+                file1.to_string(),
+                file2.to_string(),
+                window,
+                options,
+                Observers {
+                    events: Some(&events),
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        let event = match &result {
+            Ok((sections1, sections2)) => {
+                SectionizerEvent::SectionsReady(Box::new((sections1.clone(), sections2.clone())))
+            }
+            Err(e) => SectionizerEvent::Error(e.to_string()),
         };
 
-        let stream1 = self.state.create(profile, file1.to_string()).await?;
-        let stream2 = self.state.create(profile, file2.to_string()).await?;
+        let _ = events.send(event).await;
+
+        result
+    }
+
+    /// Returns a clone of `self` with the hashing overrides of `options` applied to its
+    /// configuration.
+    fn with_overrides(&self, options: &CategorizeOptions) -> Self {
+        let mut sectionizer = self.clone();
+
+        if let Some(hash_alg) = options.hash_alg {
+            sectionizer.config.hash_alg = hash_alg;
+        }
+
+        if let Some(hash_max_dist) = options.hash_max_dist {
+            sectionizer.config.hash_max_dist = hash_max_dist;
+            sectionizer.config.auto_threshold = false;
+            sectionizer.config.threshold_sweep = None;
+        }
+
+        sectionizer
+    }
+
+    /// Returns a copy of `self` fit for analyzing `window`. Full-file windows get
+    /// `FULL_FILE_HASH_STRIDE` unless a `hash_stride` is configured, so that analyzing a whole
+    /// film doesn't hash hundreds of thousands of frames.
+    fn with_window_defaults(&self, window: Window) -> Self {
+        let mut sectionizer = self.clone();
+
+        if window == Window::Full {
+            slog::warn!(self.logger, "Analyzing whole files, this is slow and memory hungry";
+                "max_frames" => self.config.max_frames,
+            );
+
+            if sectionizer.config.hash_stride <= 1 {
+                sectionizer.config.hash_stride = FULL_FILE_HASH_STRIDE;
+            }
+        }
+
+        sectionizer
+    }
+
+    /// Method `categorize_one` looks for the sections of `file` which also appear in `reference`,
+    /// like the first half of the result of [`categorize_with`](Self::categorize_with). The
+    /// sections of `reference` are not computed, which halves the matching work.
+    /// # Arguments
+    /// `file` - File path to find the sections of
+    /// `reference` - File path to match `file` against
+    /// `options` - Overrides applied on top of the `Sectionizer`'s configuration, `fps1` applies
+    /// to `file` and `fps2` to `reference`
+    pub async fn categorize_one<T: ToString>(
+        &mut self,
+        file: T,
+        reference: T,
+        reverse: bool,
+        options: CategorizeOptions,
+    ) -> Result<Sections> {
+        let window = self.config.window(reverse || self.config.reverse);
+        let windows = (
+            options.window1.unwrap_or(window),
+            options.window2.unwrap_or(window),
+        );
+        let (file, reference) = (file.to_string(), reference.to_string());
+
+        let fps1 = self.fps(&file, options.fps1).await;
+        let fps2 = self.fps(&reference, options.fps2).await;
+
+        let mut sectionizer = self.with_overrides(&options);
+        let (extraction, reference) = sectionizer
+            .extract_pair(&file, &reference, windows, fps1, fps2, Observers::default())
+            .await?;
 
-        self.state.start(stream1.clone()).await?;
-        self.state.start(stream2.clone()).await?;
+        let sections =
+            sectionizer.categorize_frames_one(extraction.frames, reference.frames, fps1, fps2);
 
-        let stream1 = self.state.take_stdout(stream1).await?;
-        let stream2 = self.state.take_stdout(stream2).await?;
+        Ok(Sections {
+            window_start: sectionizer.window_start(&file, extraction.window).await,
+            window: Some(extraction.window),
+            reverse: windows.0.is_tail(),
+            target: file,
+            ignored: extraction.ignored,
+            ..sections
+        })
+    }
+
+    /// Method `find_reference` looks for `reference`, a clip of a known section such as an
+    /// opening, in `file`. Unlike with [`categorize_one`](Self::categorize_one), the whole of
+    /// `reference` is hashed while `file` is only sampled in its head or tail window, so every
+    /// frame of the clip can be matched against.
+    ///
+    /// This is `categorize_one` with `window2` defaulting to [`Window::Full`]. The full window
+    /// doesn't get `FULL_FILE_HASH_STRIDE`, as a clip is short enough to hash every frame of.
+    pub async fn find_reference<T: ToString>(
+        &mut self,
+        file: T,
+        reference: T,
+        reverse: bool,
+        options: CategorizeOptions,
+    ) -> Result<Sections> {
+        let options = CategorizeOptions {
+            window2: options.window2.or(Some(Window::Full)),
+            ..options
+        };
+
+        self.categorize_one(file, reference, reverse, options).await
+    }
 
-        // wait for ffmpeg to spit out all the frames for both files.
-        let (framevec1, framevec2) = join!(
-            self.compute_frame_vec(stream1),
-            self.compute_frame_vec(stream2)
+    async fn run(
+        &mut self,
+        file1: String,
+        file2: String,
+        window: Window,
+        options: CategorizeOptions,
+        observers: Observers<'_>,
+    ) -> Result<(Sections, Sections)> {
+        let fps1 = self.fps(&file1, options.fps1).await;
+        let fps2 = self.fps(&file2, options.fps2).await;
+        let windows = (
+            options.window1.unwrap_or(window),
+            options.window2.unwrap_or(window),
         );
 
-        let indextree1 = self.tree_from_vec(framevec1.clone());
-        let indextree2 = self.tree_from_vec(framevec2.clone());
+        let extracted = self
+            .extract_pair(&file1, &file2, windows, fps1, fps2, observers)
+            .await;
+
+        // files are only probed for video once extracting it failed or came up next to empty, so
+        // that files with video don't pay for two extra ffprobe runs.
+        let decoded = |x: &Extraction| x.frames.len() + x.ignored.total();
+        let sparse = match &extracted {
+            Ok((a, b)) => decoded(a).min(decoded(b)) <= 1,
+            Err(_) => true,
+        };
+
+        if sparse && self.audio_only(&file1, &file2).await {
+            slog::info!(self.logger, "No video to compare, matching audio instead";
+                "file1" => &file1,
+                "file2" => &file2,
+            );
+            return self.run_audio(file1, file2, windows).await;
+        }
+
+        let (extraction1, extraction2) = extracted?;
 
-        let sections1 = self.get_sections(indextree2, framevec1);
-        let sections2 = self.get_sections(indextree1, framevec2);
+        let (sections1, sections2) = self
+            .categorize_frames_concurrently(extraction1.frames, extraction2.frames, fps1, fps2)
+            .await;
 
         Ok((
             Sections {
-                target: file1.to_string(),
-                sections: sections1,
+                window_start: self.window_start(&file1, extraction1.window).await,
+                window: Some(extraction1.window),
+                reverse: windows.0.is_tail(),
+                target: file1,
+                ignored: extraction1.ignored,
+                ..sections1
             },
             Sections {
-                target: file2.to_string(),
-                sections: sections2,
+                window_start: self.window_start(&file2, extraction2.window).await,
+                window: Some(extraction2.window),
+                reverse: windows.1.is_tail(),
+                target: file2,
+                ignored: extraction2.ignored,
+                ..sections2
             },
         ))
     }
 
-    fn get_sections(&self, indextree: BkTree<Frame>, framevec: Vec<Frame>) -> Vec<(u128, u128)> {
-        let mut framevec = framevec
-            .into_iter()
-            .filter_map(|x| indextree.find(x, HASH_MAX_DIST).first().map(|y| (x, *y.0)))
-            .collect::<Vec<_>>();
+    /// Decodes and hashes the first of `windows` of `file1` and the second of `file2`
+    /// concurrently.
+    async fn extract_pair(
+        &mut self,
+        file1: &str,
+        file2: &str,
+        (window1, window2): (Window, Window),
+        fps1: f64,
+        fps2: f64,
+        observers: Observers<'_>,
+    ) -> Result<(Extraction, Extraction)> {
+        let (file1, file2) = (file1.to_string(), file2.to_string());
+        let Observers { events, on_section } = observers;
 
-        // sort framevec to avoid overflow
-        framevec.sort_by(|x, y| x.0.idx.cmp(&y.0.idx));
+        let (window1, window2) = match &self.config.chapter {
+            Some(chapter) => (
+                self.chapter_window(&file1, chapter, window1).await,
+                self.chapter_window(&file2, chapter, window2).await,
+            ),
+            None => self.windows(&file1, &file2, (window1, window2)).await,
+        };
 
-        let mut groups: HashMap<u64, Vec<Frame>> = HashMap::new();
+        let profile1 = self.profile(&file1, window1).await?;
+        let profile2 = self.profile(&file2, window2).await?;
 
-        for frame in framevec {
-            // assumes fps is 24
-            let baseframe_idx = frame.0.idx - (frame.0.idx % 24);
-            groups.entry(baseframe_idx / 24).or_default().push(frame.0);
+        let sequential = self.config.sequential_extraction;
+        let ((mut id1, (framevec1, ignored1)), (mut id2, (framevec2, ignored2))) =
+            match self.config.early_stop {
+                // wait for ffmpeg to spit out all the frames for both files.
+                None if on_section.is_none() && !sequential => {
+                    let (id1, stream1) = self.stream(profile1, file1.clone()).await?;
+                    let (id2, stream2) = self.stream(profile2, file2.clone()).await?;
+                    send_started(events, &file1, &file2).await;
+
+                    let (extraction1, extraction2) = join!(
+                        self.compute_frame_vec(
+                            &file1,
+                            stream1,
+                            self.frame_limit(window1, fps1),
+                            progress_reporter(events, &file1),
+                            |_| false
+                        ),
+                        self.compute_frame_vec(
+                            &file2,
+                            stream2,
+                            self.frame_limit(window2, fps2),
+                            progress_reporter(events, &file2),
+                            |_| false
+                        )
+                    );
+
+                    ((id1, extraction1), (id2, extraction2))
+                }
+                // only spawn the second ffmpeg once the first one is done.
+                None if on_section.is_none() => {
+                    let (id1, stream1) = self.stream(profile1, file1.clone()).await?;
+                    send_started(events, &file1, &file2).await;
+
+                    let extraction1 = self
+                        .compute_frame_vec(
+                            &file1,
+                            stream1,
+                            self.frame_limit(window1, fps1),
+                            progress_reporter(events, &file1),
+                            |_| false,
+                        )
+                        .await;
+
+                    let (id2, stream2) = self.stream(profile2, file2.clone()).await?;
+                    let extraction2 = self
+                        .compute_frame_vec(
+                            &file2,
+                            stream2,
+                            self.frame_limit(window2, fps2),
+                            progress_reporter(events, &file2),
+                            |_| false,
+                        )
+                        .await;
+
+                    ((id1, extraction1), (id2, extraction2))
+                }
+                // the second file has to be complete to match the first one against it as it streams.
+                early_stop => {
+                    let (id2, stream2) = self.stream(profile2, file2.clone()).await?;
+                    let stream1 = if sequential {
+                        None
+                    } else {
+                        Some(self.stream(profile1, file1.clone()).await?)
+                    };
+                    send_started(events, &file1, &file2).await;
+
+                    let (framevec2, ignored2) = self
+                        .compute_frame_vec(
+                            &file2,
+                            stream2,
+                            self.frame_limit(window2, fps2),
+                            progress_reporter(events, &file2),
+                            |_| false,
+                        )
+                        .await;
+
+                    let (id1, stream1) = match stream1 {
+                        Some(x) => x,
+                        None => self.stream(profile1, file1.clone()).await?,
+                    };
+
+                    let tree = self.tree_from_vec(self.aggregate(framevec2.clone(), fps2));
+                    let reported = RefCell::new(BTreeSet::new());
+                    let stop = |frames: &[Frame]| {
+                        let finished = self.finished_groups(&tree, frames, fps1);
+
+                        if let Some(on_section) = on_section {
+                            self.report_sections(&finished, frames, fps1, &reported, on_section);
+                        }
+
+                        match early_stop {
+                            Some(early_stop) => self.section_confirmed(&finished, early_stop),
+                            None => false,
+                        }
+                    };
+                    let extraction1 = self
+                        .compute_frame_vec(
+                            &file1,
+                            stream1,
+                            self.frame_limit(window1, fps1),
+                            progress_reporter(events, &file1),
+                            stop,
+                        )
+                        .await;
+
+                    ((id1, extraction1), (id2, (framevec2, ignored2)))
+                }
+            };
+
+        if let Some(events) = events {
+            for (file, frames) in [(&file1, &framevec1), (&file2, &framevec2)] {
+                let _ = events
+                    .send(SectionizerEvent::FileDone {
+                        file: file.clone(),
+                        frames: frames.last().map(|x| x.idx + 1).unwrap_or(0),
+                    })
+                    .await;
+            }
         }
 
-        let mut groups = groups
-            .into_iter()
-            .filter(|(_, x)| x.len() > 1)
-            .collect::<Vec<_>>();
+        self.check_extraction(&mut id1, &file1, framevec1.len() + ignored1.total())
+            .await?;
+        self.check_extraction(&mut id2, &file2, framevec2.len() + ignored2.total())
+            .await?;
 
-        groups.sort_by(|a, b| a.0.cmp(&b.0));
+        // ffmpeg may still be decoding the rest of the window if we stopped early.
+        if self.config.early_stop.is_some() {
+            self.kill(id1).await;
+        }
 
-        groups
-            .group_by_mut(|(a, _), (b, _)| b - a <= 5)
-            .map(|x| {
-                x.sort_by_key(|(a, _)| *a);
+        self.log_extracted(&file1, framevec1.len(), ignored1);
+        self.log_extracted(&file2, framevec2.len(), ignored2);
+
+        Ok((
+            Extraction {
+                frames: framevec1,
+                ignored: ignored1,
+                window: window1,
+            },
+            Extraction {
+                frames: framevec2,
+                ignored: ignored2,
+                window: window2,
+            },
+        ))
+    }
 
-                let first = x.first().map(|(x, _)| *x).unwrap_or(0);
+    /// Method `frames_from_image_dir` hashes the images of `dir`, frames extracted beforehand such
+    /// as with `ffmpeg -i <file> %06d.png`, into frames which can be handed to
+    /// [`categorize_frames`](Self::categorize_frames). This makes the whole matching pipeline
+    /// reproducible without ffmpeg.
+    ///
+    /// Images are ordered by the number in their file name and indexed from `0` in that order,
+    /// files which aren't images are skipped. Every image is scaled to `frame_size` and then goes
+    /// through the same steps as a decoded frame, including `hash_stride` and `skip_solid_frames`.
+    pub async fn frames_from_image_dir(
+        &self,
+        dir: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<Frame>> {
+        let dir = dir.as_ref().to_path_buf();
+        let frame_size = self.config.frame_size;
 
-                x.iter()
-                    .map(|(x, _)| *x)
-                    .fold((first, 0), |(f, _), x| (f, x))
-            })
-            .map(|x| (x.0 as u128, x.1 as u128))
-            .collect::<Vec<_>>()
+        // listing and decoding the images is blocking work, keep it off the runtime's threads.
+        let raw = tokio::task::spawn_blocking({
+            let dir = dir.clone();
+            move || read_image_dir(&dir, frame_size)
+        })
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+
+        let file = dir.to_string_lossy();
+        let (frames, ignored) = self
+            .compute_frame_vec(&file, raw.as_slice(), None, |_| {}, |_| false)
+            .await;
+        self.log_extracted(&file, frames.len(), ignored);
+
+        Ok(frames)
     }
 
-    async fn compute_frame_vec(&self, mut stream: ChildStdout) -> Vec<Frame> {
-        let mut frames = Vec::with_capacity(240 * 24);
-        let mut buf: Box<[u8; IMG_SIZE]> = box [0; IMG_SIZE];
+    /// Method `categorize_frames` runs the matching stage of [`categorize`](Self::categorize) on
+    /// frames which have already been extracted, without touching ffmpeg. The frames don't have
+    /// to start at index `0` or be contiguous, see [`frames`](crate::frames) for cutting them down.
+    /// # Arguments
+    /// `frames1` - Frames of the first file
+    /// `frames2` - Frames of the second file
+    /// `fps1` - Frame rate of the first file
+    /// `fps2` - Frame rate of the second file
+    ///
+    /// # Returns
+    /// The sections for `frames1` and `frames2`, their `target` is left empty and their sections
+    /// are relative to the frame with index `0`.
+    pub fn categorize_frames(
+        &self,
+        frames1: Vec<Frame>,
+        frames2: Vec<Frame>,
+        fps1: f64,
+        fps2: f64,
+    ) -> (Sections, Sections) {
+        let (analyzed1, analyzed2) = (frames1.len(), frames2.len());
+        let enough = self.analyzed_enough(analyzed1, fps1) && self.analyzed_enough(analyzed2, fps2);
 
-        let hasher = img_hash::HasherConfig::with_bytes_type::<[u8; 16]>()
-            .hash_alg(HASHER)
-            .preproc_dct()
-            .to_hasher();
+        let frames1 = self.aggregate(frames1, fps1);
+        let frames2 = self.aggregate(frames2, fps2);
 
-        let mut idx = 0u64;
+        let max_dist = self.threshold(&frames1, &frames2, fps1);
 
-        while stream.read_exact(buf.as_mut()).await.is_ok() {
-            let raw: &[u8] = buf.as_ref();
+        let indextree1 = self.tree_from_vec(frames1.clone());
+        let indextree2 = self.tree_from_vec(frames2.clone());
 
-            let frame =
-                image::RgbImage::from_raw(IMG_W as u32, IMG_H as u32, raw.to_vec()).unwrap();
+        let (detection1, detection2) = self.cross_validate(
+            self.detect(indextree2, frames1, self.params(max_dist, fps1)),
+            self.detect(indextree1, frames2, self.params(max_dist, fps2)),
+        );
 
-            let hash = hasher.hash_image(&frame);
-            let hash = u128::from_be_bytes(hash.as_bytes().try_into().unwrap());
+        (
+            frame_sections(self.seconds(detection1), analyzed1, fps1, enough),
+            frame_sections(self.seconds(detection2), analyzed2, fps2, enough),
+        )
+    }
 
-            let frame = Frame { hash, idx };
-            frames.push(frame);
-            idx += 1;
-        }
+    /// Behaves like [`categorize_frames`](Self::categorize_frames), but builds the trees and
+    /// matches both directions concurrently on the blocking thread pool of tokio.
+    async fn categorize_frames_concurrently(
+        &self,
+        frames1: Vec<Frame>,
+        frames2: Vec<Frame>,
+        fps1: f64,
+        fps2: f64,
+    ) -> (Sections, Sections) {
+        let (analyzed1, analyzed2) = (frames1.len(), frames2.len());
+        let enough = self.analyzed_enough(analyzed1, fps1) && self.analyzed_enough(analyzed2, fps2);
 
-        frames
+        let frames1 = self.aggregate(frames1, fps1);
+        let frames2 = self.aggregate(frames2, fps2);
+
+        let max_dist = self.threshold(&frames1, &frames2, fps1);
+
+        // trees can't be sent across threads, so each task builds the one it matches against.
+        let direction = |frames: Vec<Frame>, reference: Vec<Frame>, fps: f64| {
+            let sectionizer = self.clone();
+
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    let indextree = sectionizer.tree_from_vec(reference);
+                    sectionizer.detect(indextree, frames, sectionizer.params(max_dist, fps))
+                })
+                .await
+                .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+            }
+        };
+
+        let (detection1, detection2) = join!(
+            direction(frames1.clone(), frames2.clone(), fps1),
+            direction(frames2, frames1, fps2)
+        );
+        let (detection1, detection2) = self.cross_validate(detection1, detection2);
+
+        (
+            frame_sections(self.seconds(detection1), analyzed1, fps1, enough),
+            frame_sections(self.seconds(detection2), analyzed2, fps2, enough),
+        )
     }
 
-    fn tree_from_vec(&self, frames: Vec<Frame>) -> BkTree<Frame> {
-        let mut tree = BkTree::new(hamming);
-        tree.insert_all(frames);
+    /// Method `categorize_frames_one` is the single direction counterpart of
+    /// [`categorize_frames`](Self::categorize_frames), returning only the sections of `frames`
+    /// which also appear in `reference`.
+    pub fn categorize_frames_one(
+        &self,
+        frames: Vec<Frame>,
+        reference: Vec<Frame>,
+        fps: f64,
+        reference_fps: f64,
+    ) -> Sections {
+        let analyzed = frames.len();
+        let enough = self.analyzed_enough(analyzed, fps)
+            && self.analyzed_enough(reference.len(), reference_fps);
 
-        tree
+        let frames = self.aggregate(frames, fps);
+        let reference = self.aggregate(reference, reference_fps);
+
+        let max_dist = self.threshold(&frames, &reference, fps);
+        let indextree = self.tree_from_vec(reference);
+        let sections = self.get_sections(indextree, frames, self.params(max_dist, fps));
+
+        frame_sections(sections, analyzed, fps, enough)
     }
-}
 
-pub struct Sections {
-    pub target: String,
-    pub sections: Vec<(u128, u128)>,
-}
+    /// Returns whether `frames` frames cover enough time for an empty result to be trusted.
+    fn analyzed_enough(&self, frames: usize, fps: f64) -> bool {
+        frames as f64 >= self.config.min_analyzed_secs * self.hashed_fps(fps)
+    }
 
-pub fn hamming(a: &Frame, b: &Frame) -> isize {
-    (a.hash ^ b.hash).count_ones() as isize
+    /// Returns the number of frames hashed per second of a stream decoded at `fps`, which is
+    /// lower than `fps` with a `hash_stride`.
+    fn hashed_fps(&self, fps: f64) -> f64 {
+        fps / self.config.hash_stride.max(1) as f64
+    }
+
+    /// Method `best_opening` returns the single section of `file1` most likely to be its opening.
+    ///
+    /// Only sections starting within `opening_max_start_secs` of the start of the file are
+    /// considered, out of which one is picked according to `section_preference`.
+    ///
+    /// # Returns
+    /// The opening of `file1` in seconds, or `None` if no section qualifies.
+    pub async fn best_opening<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+    ) -> Result<Option<(u128, u128)>> {
+        let (sections, _) = self.categorize(file1, file2, false).await?;
+
+        let candidates = sections
+            .with_confidence()
+            .into_iter()
+            .filter(|((start, _), _)| *start <= self.config.opening_max_start_secs);
+
+        Ok(self.config.section_preference.pick(candidates))
+    }
+
+    /// Method `best_match` finds which of `references` shares the most with `target`, for example
+    /// to deduplicate a new upload against a library. `target` is only decoded and hashed once,
+    /// however many references there are, and the frames of the references are kept in the frame
+    /// cache of [`categorize_cached`](Self::categorize_cached) across calls.
+    ///
+    /// References which fail to extract are skipped with a warning.
+    ///
+    /// # Arguments
+    /// `target` - File path to find the best reference for
+    /// `references` - File paths to match `target` against
+    /// `options` - Overrides applied on top of the `Sectionizer`'s configuration, `window1` and
+    /// `fps1` apply to `target` while `window2` and `fps2` apply to every reference
+    ///
+    /// # Returns
+    /// The reference with the longest total matched duration along with the sections of `target`
+    /// it matches, or `None` if no reference matches at all. Fails if `target` or every one of
+    /// `references` does.
+    pub async fn best_match(
+        &mut self,
+        target: String,
+        references: Vec<String>,
+        reverse: bool,
+        options: CategorizeOptions,
+    ) -> Result<Option<(String, Sections)>> {
+        let window = self.config.window(reverse || self.config.reverse);
+        let (window, reference_window) = (
+            options.window1.unwrap_or(window),
+            options.window2.unwrap_or(window),
+        );
+        let fps = self.fps(&target, options.fps1).await;
+
+        let mut sectionizer = self.with_overrides(&options);
+        let (framevec, ignored) = sectionizer.extract(&target, window, fps).await?;
+        let window_start = sectionizer.window_start(&target, window).await;
+
+        let mut best: Option<(String, Sections, u128)> = None;
+        let mut error = None;
+        let mut extracted = 0;
+
+        for reference in references {
+            let reference_fps = sectionizer.fps(&reference, options.fps2).await;
+            let reference_frames = match sectionizer
+                .cached_frames(&reference, reference_window, reference_fps)
+                .await
+            {
+                Ok((frames, _)) => frames,
+                Err(e) => {
+                    slog::warn!(
+                        sectionizer.logger,
+                        "Skipping reference {}: {}",
+                        reference,
+                        e
+                    );
+                    error = Some(e);
+                    continue;
+                }
+            };
+            extracted += 1;
+
+            let sections = sectionizer.categorize_frames_one(
+                framevec.clone(),
+                reference_frames,
+                fps,
+                reference_fps,
+            );
+
+            let matched = sections
+                .sections
+                .iter()
+                .map(|(start, end)| end - start)
+                .sum::<u128>();
+
+            if sections.sections.is_empty() || matches!(&best, Some((_, _, x)) if *x >= matched) {
+                continue;
+            }
+
+            let sections = Sections {
+                target: target.clone(),
+                window_start,
+                window: Some(window),
+                reverse: window.is_tail(),
+                ignored,
+                ..sections
+            };
+
+            best = Some((reference, sections, matched));
+        }
+
+        match error {
+            Some(e) if extracted == 0 => Err(e),
+            _ => Ok(best.map(|(reference, sections, _)| (reference, sections))),
+        }
+    }
+
+    /// Method `self_sections` finds segments which repeat within `file` itself, such as recurring
+    /// transitions or sponsor bumpers.
+    ///
+    /// The frames of the file are matched against themselves, ignoring any match closer than
+    /// `self_match_min_gap_secs` so that a frame cannot match itself or its immediate neighbours.
+    ///
+    /// # Returns
+    /// The sections of `file`, in seconds, which also appear elsewhere in the analyzed window.
+    pub async fn self_sections(&mut self, file: String) -> Result<Vec<(u128, u128)>> {
+        let fps = self.fps(&file, None).await;
+        let window = self.config.window(false);
+        let (framevec, _) = self.extract(&file, window, fps).await?;
+        let indextree = self.tree_from_vec(framevec.clone());
+
+        let params = MatchParams {
+            min_separation: (self.config.self_match_min_gap_secs as f64 * fps) as u64,
+            ..self.params(self.config.hash_max_dist, fps)
+        };
+
+        Ok(self.get_sections(indextree, framevec, params).0)
+    }
+
+    /// Method `scrolling_credits` looks for scrolling end credits in the tail window of `file`
+    /// from the motion between its frames, see [`motion`](crate::motion). This complements
+    /// matching, which only finds credits shared with another file and copes poorly with
+    /// scrolling, as every frame of it hashes differently.
+    ///
+    /// # Returns
+    /// A [`Marker`] of [`MarkerKind::Credits`] for every run of steady scrolling, in absolute time.
+    pub async fn scrolling_credits(&mut self, file: String) -> Result<Vec<Marker>> {
+        if !is_url(&file) && !std::path::Path::new(&file).exists() {
+            return Err(SectionizerError::FileNotFound(file));
+        }
+
+        let window = self.config.window(true);
+        let (tt, sseof) = self.bounds(&file, window).await?;
+        let (mut session, stream) =
+            self.spawn_direct(&file, motion::ffmpeg_args(&file, tt, sseof))?;
+
+        let mut frames = motion::read_frames(stream).await;
+        self.check_extraction(&mut session, &file, frames.len())
+            .await?;
+
+        if let Some(limit) = self.frame_limit(window, motion::FPS) {
+            frames.truncate(limit as usize);
+        }
+
+        let window_start = self.window_start(&file, window).await;
+        let secs = |idx: u64| window_start + frame_to_secs(idx, motion::FPS).round() as u128;
+
+        Ok(motion::scrolling_runs(&frames)
+            .into_iter()
+            .map(|(first, last)| Marker {
+                kind: MarkerKind::Credits,
+                start: secs(first),
+                end: secs(last),
+            })
+            .collect())
+    }
+
+    /// Method `frame_at` decodes the frame of `file` shown at `secs`, at its full resolution, for
+    /// example to show what a detected boundary looks like.
+    ///
+    /// Nightfall sessions can't seek from the start of a file, so this runs the configured ffmpeg
+    /// directly. It is independent of the matching pipeline and ignores the crop and window.
+    pub async fn frame_at(&self, file: String, secs: f64) -> Result<image::RgbImage> {
+        if !is_url(&file) && !std::path::Path::new(&file).exists() {
+            return Err(SectionizerError::FileNotFound(file));
+        }
+
+        if self.state.is_none() {
+            return Err(SectionizerError::NoFfmpeg);
+        }
+
+        let output = tokio::process::Command::new(&self.config.ffmpeg)
+            .args([
+                "-v",
+                "error",
+                "-ss",
+                &secs.max(0.0).to_string(),
+                "-i",
+                &file,
+            ])
+            .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"])
+            .output()
+            .await?;
+
+        if output.stdout.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            return Err(SectionizerError::ExtractionFailed(file, stderr));
+        }
+
+        image::load_from_memory_with_format(&output.stdout, image::ImageFormat::Png)
+            .map(|x| x.to_rgb8())
+            .map_err(|e| SectionizerError::ExtractionFailed(file, e.to_string()))
+    }
+
+    /// Method `fingerprint` takes a fingerprint of `section` of `file`, for example a confirmed
+    /// opening, which can later be matched against with [`find_template`](Self::find_template).
+    /// # Arguments
+    /// `file` - File path to take the fingerprint from
+    /// `section` - Section in seconds, relative to the analyzed window
+    /// `reverse` - Whether `section` lies in the tail window of the file
+    /// `label` - Label to attach to the fingerprint
+    pub async fn fingerprint(
+        &mut self,
+        file: String,
+        section: (u128, u128),
+        reverse: bool,
+        label: String,
+    ) -> Result<Fingerprint> {
+        let fps = self.fps(&file, None).await;
+        let window = self.config.window(reverse);
+        let (framevec, _) = self.extract(&file, window, fps).await?;
+
+        let frames = framevec
+            .into_iter()
+            .filter(|x| {
+                let second = frame_to_secs(x.idx, fps) as u128;
+                second >= section.0 && second <= section.1
+            })
+            .collect();
+
+        Ok(Fingerprint {
+            label,
+            frames,
+            fps,
+            reverse,
+            hash_max_dist: self.config.hash_max_dist,
+        })
+    }
+
+    /// Method `find_template` looks for the section described by `template` in `file`, without
+    /// having to compare `file` against another episode.
+    ///
+    /// # Returns
+    /// The sections of `file` which match the template, along with how much of the template each
+    /// of them covers, see [`Sections::coverage`].
+    pub async fn find_template(
+        &mut self,
+        file: String,
+        template: &Fingerprint,
+    ) -> Result<Sections> {
+        let fps = self.fps(&file, None).await;
+        let window = self.config.window(template.reverse);
+        let (framevec, ignored) = self.extract(&file, window, fps).await?;
+
+        let analyzed = framevec.len();
+        let enough = self.analyzed_enough(analyzed, fps);
+
+        let indextree = self.tree_from_vec(template.frames.clone());
+        let (sections, quality) = self.get_sections(
+            indextree,
+            framevec,
+            self.params(template.hash_max_dist, fps),
+        );
+
+        let reference = template.secs();
+        let coverage = sections
+            .iter()
+            .map(|(start, end)| match reference {
+                x if x > 0.0 => ((end - start) as f64 / x).min(1.0),
+                _ => 1.0,
+            })
+            .collect();
+
+        Ok(Sections {
+            window_start: self
+                .window_start(&file, self.config.window(template.reverse))
+                .await,
+            target: file,
+            status: AnalysisStatus::new(!sections.is_empty(), enough, quality.rejected > 0),
+            sections,
+            quality,
+            coverage,
+            ignored,
+            analyzed,
+            window: Some(self.config.window(template.reverse)),
+            reverse: template.reverse,
+            fps,
+        })
+    }
+
+    /// Method `find_stored_template` loads the fingerprint stored under `id` in `store` and
+    /// matches `file` against it with [`find_template`](Self::find_template).
+    ///
+    /// # Returns
+    /// `None` if there is no fingerprint stored under `id`.
+    pub async fn find_stored_template(
+        &mut self,
+        store: &impl FingerprintStore,
+        id: &str,
+        file: String,
+    ) -> Result<Option<Sections>> {
+        let template = store.load(id)?;
+        self.stats.add_cache_lookup(template.is_some());
+
+        match template {
+            Some(template) => Ok(Some(self.find_template(file, &template).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Method `categorize_cached` behaves like [`categorize`](Self::categorize), but takes the
+    /// frames of each file from the frame cache in `cache_dir` if they were extracted before with
+    /// the same settings, only decoding files on a miss and caching their frames. See
+    /// [`cache`](crate::cache) for when an entry is used.
+    ///
+    /// Files are extracted one after the other and then matched like with
+    /// [`categorize_frames`](Self::categorize_frames), so `early_stop` doesn't apply. Inputs which
+    /// can't be cached, such as URLs, are extracted every time.
+    pub async fn categorize_cached<T: ToString>(
+        &mut self,
+        file1: T,
+        file2: T,
+        reverse: bool,
+    ) -> Result<(Sections, Sections)> {
+        let reverse = reverse || self.config.reverse;
+        let (file1, file2) = (file1.to_string(), file2.to_string());
+
+        let (fps1, fps2) = (self.fps(&file1, None).await, self.fps(&file2, None).await);
+        let window = self.config.window(reverse);
+        let (frames1, ignored1) = self.cached_frames(&file1, window, fps1).await?;
+        let (frames2, ignored2) = self.cached_frames(&file2, window, fps2).await?;
+
+        let (sections1, sections2) = self
+            .categorize_frames_concurrently(frames1, frames2, fps1, fps2)
+            .await;
+
+        Ok((
+            Sections {
+                window_start: self.window_start(&file1, window).await,
+                window: Some(window),
+                reverse,
+                target: file1,
+                ignored: ignored1,
+                ..sections1
+            },
+            Sections {
+                window_start: self.window_start(&file2, window).await,
+                window: Some(window),
+                reverse,
+                target: file2,
+                ignored: ignored2,
+                ..sections2
+            },
+        ))
+    }
+
+    /// Returns the frames of `window` of `file` from the frame cache, extracting them at `fps`
+    /// and caching them on a miss. Failing to read or write the cache only costs an extraction.
+    async fn cached_frames(
+        &mut self,
+        file: &str,
+        window: Window,
+        fps: f64,
+    ) -> Result<(Vec<Frame>, IgnoredFrames)> {
+        let dir = std::path::Path::new(&self.config.cache_dir).join(FRAME_CACHE_DIR);
+        let key = CacheKey::new(&self.config, file, window, fps);
+
+        if let Some(key) = &key {
+            let cached = cache::load(&dir, key).unwrap_or_else(|e| {
+                slog::warn!(
+                    self.logger,
+                    "Failed to read cached frames of {}: {}",
+                    file,
+                    e
+                );
+                None
+            });
+            self.stats.add_cache_lookup(cached.is_some());
+
+            if let Some(cached) = cached {
+                return Ok((cached.frames, cached.ignored));
+            }
+        }
+
+        let checkpoint = self
+            .extract_resumable(
+                file.to_string(),
+                window.is_tail(),
+                Some(Checkpoint::new(file.to_string(), window, fps)),
+                &AtomicBool::new(false),
+            )
+            .await?;
+
+        if let Some(key) = key {
+            let entry = CachedFrames {
+                key,
+                frames: checkpoint.frames,
+                ignored: checkpoint.ignored,
+            };
+
+            if let Err(e) = cache::save(&dir, &entry) {
+                slog::warn!(self.logger, "Failed to cache frames of {}: {}", file, e);
+            }
+
+            return Ok((entry.frames, entry.ignored));
+        }
+
+        Ok((checkpoint.frames, checkpoint.ignored))
+    }
+
+    /// Method `extract_resumable` decodes and hashes the analyzed window of `file` until it is
+    /// done or `cancel` is set, returning a [`Checkpoint`] of the frames extracted so far.
+    ///
+    /// Passing a checkpoint which is not `done` back in seeks ffmpeg to where it stopped and
+    /// appends the rest of the window to it. Seeking is only accurate to the second, frames which
+    /// were already extracted are dropped.
+    /// # Arguments
+    /// `file` - File path to extract the frames of
+    /// `reverse` - Whether to extract the tail window, ignored when resuming
+    /// `checkpoint` - Checkpoint to resume from, `None` to start from scratch
+    /// `cancel` - Flag which stops extraction when set, checked every `PROGRESS_INTERVAL` frames
+    pub async fn extract_resumable(
+        &mut self,
+        file: String,
+        reverse: bool,
+        checkpoint: Option<Checkpoint>,
+        cancel: &AtomicBool,
+    ) -> Result<Checkpoint> {
+        let mut checkpoint = match checkpoint {
+            Some(x) => x,
+            None => {
+                let fps = self.fps(&file, None).await;
+                Checkpoint::new(file.clone(), self.config.window(reverse), fps)
+            }
+        };
+
+        if checkpoint.done {
+            return Ok(checkpoint);
+        }
+
+        let fps = checkpoint.fps;
+        let offset = frame_to_secs(checkpoint.next_idx, fps).floor() as usize;
+
+        let window = match checkpoint.window {
+            window if offset == 0 => window,
+            Window::Head(secs) => Window::Span {
+                start: offset,
+                secs: secs.saturating_sub(offset),
+            },
+            Window::Span { start, secs } => Window::Span {
+                start: start + offset,
+                secs: secs.saturating_sub(offset),
+            },
+            Window::Tail(secs) => Window::Span {
+                start: self.window_start(&file, Window::Tail(secs)).await as usize + offset,
+                secs: secs.saturating_sub(offset),
+            },
+            // a span running past the end of the file reads up to the end.
+            Window::Full => Window::Span {
+                start: offset,
+                secs: usize::MAX,
+            },
+        };
+
+        let profile = self.profile(&file, window).await?;
+        let (mut id, stream) = self.stream(profile, file.clone()).await?;
+        let (framevec, ignored) = self
+            .compute_frame_vec(
+                &file,
+                stream,
+                self.frame_limit(window, fps),
+                |_| {},
+                |_| cancel.load(Ordering::Relaxed),
+            )
+            .await;
+
+        let decoded = framevec.len() + ignored.total();
+        let cancelled = cancel.load(Ordering::Relaxed);
+
+        if cancelled {
+            self.kill(id).await;
+        } else if checkpoint.next_idx == 0 {
+            self.check_extraction(&mut id, &file, decoded).await?;
+        }
+
+        let first_idx = (offset as f64 * fps).round() as u64;
+        let next_idx = checkpoint.next_idx;
+
+        checkpoint.frames.extend(
+            framevec
+                .into_iter()
+                .map(|x| Frame {
+                    idx: x.idx + first_idx,
+                    ..x
+                })
+                .filter(|x| x.idx >= next_idx),
+        );
+        checkpoint.ignored.black += ignored.black;
+        checkpoint.ignored.solid += ignored.solid;
+        checkpoint.ignored.strided += ignored.strided;
+        checkpoint.next_idx = next_idx.max(first_idx + decoded as u64);
+        checkpoint.done = !cancelled;
+
+        Ok(checkpoint)
+    }
+
+    /// Decodes and hashes `window` of `file`, ready for matching.
+    async fn extract(
+        &mut self,
+        file: &str,
+        window: Window,
+        fps: f64,
+    ) -> Result<(Vec<Frame>, IgnoredFrames)> {
+        let profile = self.profile(file, window).await?;
+        let (mut id, stream) = self.stream(profile, file.to_string()).await?;
+        let (framevec, ignored) = self
+            .compute_frame_vec(
+                file,
+                stream,
+                self.frame_limit(window, fps),
+                |_| {},
+                |_| false,
+            )
+            .await;
+
+        self.check_extraction(&mut id, file, framevec.len() + ignored.total())
+            .await?;
+        self.log_extracted(file, framevec.len(), ignored);
+
+        Ok((self.aggregate(framevec, fps), ignored))
+    }
+
+    fn params(&self, max_dist: isize, fps: f64) -> MatchParams {
+        let min_bucket_matches = match self.config.granularity {
+            // a wide `hash_stride` may leave a single hashed frame per bucket.
+            Granularity::Frame if self.hashed_fps(fps) * self.config.bucket_secs < 2.0 => 1,
+            Granularity::Frame => 2,
+            // there is only ever a single hash per second.
+            Granularity::Scene => 1,
+        };
+
+        let mut merge_gap = (self.config.merge_gap_secs / self.config.bucket_secs).round() as u64;
+        // buckets `n` apart have `n - 1` empty buckets between them.
+        if let Some(min_gap) = self.config.min_section_gap_secs {
+            merge_gap = merge_gap.min((min_gap / self.config.bucket_secs).ceil().max(1.0) as u64);
+        }
+
+        MatchParams {
+            max_dist,
+            min_separation: 0,
+            fps,
+            bucket_secs: self.config.bucket_secs,
+            min_bucket_matches,
+            merge_gap,
+        }
+    }
+
+    /// Collapses `frames` into one hash per second when matching at the scene level. Each bit of
+    /// the scene hash is set if it is set in the majority of the frames of that second, which
+    /// smooths out per-frame encoding jitter. The scene takes the index of its first frame.
+    fn aggregate(&self, frames: Vec<Frame>, fps: f64) -> Vec<Frame> {
+        if self.config.granularity == Granularity::Frame {
+            return frames;
+        }
+
+        frames
+            .group_by(|a, b| frame_to_secs(a.idx, fps) as u64 == frame_to_secs(b.idx, fps) as u64)
+            .map(|scene| {
+                let width = scene[0].hash.width();
+                let hash = FrameHash::from_bits(
+                    width,
+                    (0..width.bits()).filter(|bit| {
+                        let ones = scene.iter().filter(|x| x.hash.bit(*bit)).count();
+                        ones * 2 > scene.len()
+                    }),
+                );
+
+                Frame {
+                    hash,
+                    idx: scene[0].idx,
+                    histogram: Histogram::average(
+                        scene.iter().filter_map(|x| x.histogram.as_ref()),
+                    ),
+                    run: 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the ffmpeg profile decoding `window` of `file`.
+    async fn profile(&self, file: &str, window: Window) -> Result<StreamType> {
+        let (tt, sseof) = self.bounds(file, window).await?;
+
+        let profile = RawVideoProfile::RawRgb;
+        check_profile(&profile)?;
+
+        Ok(StreamType::RawVideo {
+            map: 0,
+            profile,
+            tt,
+            sseof,
+        })
+    }
+
+    /// Returns the duration to decode and the number of seconds before the end of `file` to start
+    /// decoding at for `window`. nightfall can only seek relative to the end of the file for raw
+    /// video, so spans are read from `start` to the end of the file and cut short by
+    /// [`frame_limit`](Self::frame_limit), which requires the duration of the file.
+    async fn bounds(&self, file: &str, window: Window) -> Result<(Option<usize>, Option<i64>)> {
+        Ok(match window {
+            Window::Head(secs) => (Some(secs), None),
+            Window::Full => (None, None),
+            Window::Tail(secs) => (None, Some(secs as i64)),
+            Window::Span { start, .. } => {
+                let duration = probe::duration(&self.config.ffprobe, file).await?;
+                (None, Some((duration - start as f64).ceil().max(0.0) as i64))
+            }
+        })
+    }
+
+    /// Returns the number of frames making up `window`, if it ends before the end of the stream.
+    fn frame_limit(&self, window: Window, fps: f64) -> Option<u64> {
+        match window {
+            Window::Span { secs, .. } => Some((secs as f64 * fps).ceil() as u64),
+            _ => None,
+        }
+    }
+
+    /// Returns the windows to analyze for `file1` and `file2`, out of the requested `windows`.
+    ///
+    /// If `max_duration_ratio` is set the durations of both files are compared first, warning if
+    /// they are too far apart for a comparison to be meaningful. With `scale_windows` the window
+    /// of the longer file is then stretched so that both windows cover the same fraction of their
+    /// file.
+    async fn windows(
+        &self,
+        file1: &str,
+        file2: &str,
+        windows: (Window, Window),
+    ) -> (Window, Window) {
+        let max_ratio = match self.config.max_duration_ratio {
+            Some(x) => x,
+            None => return windows,
+        };
+
+        let durations = futures::try_join!(
+            probe::duration(&self.config.ffprobe, file1),
+            probe::duration(&self.config.ffprobe, file2)
+        );
+
+        let (duration1, duration2) = match durations {
+            Ok((a, b)) if a > 0.0 && b > 0.0 => (a, b),
+            _ => {
+                slog::debug!(
+                    self.logger,
+                    "Failed to probe durations, skipping duration check"
+                );
+                return windows;
+            }
+        };
+
+        let ratio = duration1.max(duration2) / duration1.min(duration2);
+        if ratio <= max_ratio {
+            return windows;
+        }
+
+        slog::warn!(
+            self.logger,
+            "Durations of {} ({}s) and {} ({}s) differ by a factor of {:.2}, sections are likely meaningless",
+            file1,
+            duration1 as u64,
+            file2,
+            duration2 as u64,
+            ratio
+        );
+
+        if !self.config.scale_windows {
+            return windows;
+        }
+
+        let (window1, window2) = windows;
+        if duration1 > duration2 {
+            (window1.scale(ratio), window2)
+        } else {
+            (window1, window2.scale(ratio))
+        }
+    }
+
+    /// Returns the window covering `chapter` of `file`, or `fallback` if `file` has no such
+    /// chapter.
+    async fn chapter_window(
+        &self,
+        file: &str,
+        chapter: &ChapterSelector,
+        fallback: Window,
+    ) -> Window {
+        let chapters = match probe::chapters(&self.config.ffprobe, file).await {
+            Ok(x) => x,
+            Err(e) => {
+                slog::warn!(self.logger, "Failed to probe chapters of {}: {}", file, e);
+                return fallback;
+            }
+        };
+
+        let found = match chapter {
+            ChapterSelector::Index(idx) => chapters.get(*idx),
+            ChapterSelector::Title(title) => chapters
+                .iter()
+                .find(|x| matches!(&x.title, Some(x) if x.eq_ignore_ascii_case(title))),
+        };
+
+        match found {
+            Some(x) if x.end > x.start => Window::Span {
+                start: x.start.floor() as usize,
+                secs: (x.end - x.start.floor()).ceil() as usize,
+            },
+            _ => {
+                slog::debug!(self.logger, "Chapter not found, using the regular window";
+                    "file" => file,
+                    "chapter" => ?chapter,
+                );
+                fallback
+            }
+        }
+    }
+
+    /// Spawns ffmpeg for `file` and returns its session along with the pipe the raw frames are
+    /// written to.
+    async fn stream(
+        &mut self,
+        profile: StreamType,
+        file: String,
+    ) -> Result<(Session, ChildStdout)> {
+        // catch typos early rather than through an empty stream, remote inputs are left to ffmpeg.
+        if !is_url(&file) && !std::path::Path::new(&file).exists() {
+            return Err(SectionizerError::FileNotFound(file));
+        }
+
+        if self.state.is_none() {
+            return Err(SectionizerError::NoFfmpeg);
+        }
+
+        let filter = self.deinterlace_filter(&file).await;
+        let tonemap = self.tonemap(&file).await;
+        if filter.is_some() || tonemap || self.config.frame_size != (IMG_W as u32, IMG_H as u32) {
+            let args = direct_args(profile, &file, filter, tonemap, self.config.frame_size);
+            return self.spawn_direct(&file, args);
+        }
+
+        let state = self.state.as_mut().ok_or(SectionizerError::NoFfmpeg)?;
+
+        let id = state.create(profile, file).await?;
+        state.start(id.clone()).await?;
+
+        let stdout = state.take_stdout(id.clone()).await?;
+
+        Ok((Session::Managed(id), stdout))
+    }
+
+    /// Spawns the configured ffmpeg with `args` for `file` itself, bypassing the `StateManager`.
+    fn spawn_direct(&self, file: &str, args: Vec<String>) -> Result<(Session, ChildStdout)> {
+        if self.state.is_none() {
+            return Err(SectionizerError::NoFfmpeg);
+        }
+
+        let mut child = tokio::process::Command::new(&self.config.ffmpeg)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdout = child.stdout.take().ok_or_else(|| {
+            SectionizerError::ExtractionFailed(file.to_string(), "ffmpeg has no stdout".into())
+        })?;
+
+        Ok((Session::Direct(child), stdout))
+    }
+
+    /// Returns whether `file1` or `file2` has no video stream, in which case both are matched on
+    /// their audio. Files which can't be probed are assumed to have video. A single cover art
+    /// frame is all the video extraction gets out of an audio file with one, which is why `run`
+    /// probes as soon as a file yields at most one frame.
+    async fn audio_only(&self, file1: &str, file2: &str) -> bool {
+        for file in [file1, file2] {
+            match probe::has_video(&self.config.ffprobe, file).await {
+                Ok(false) => return true,
+                Ok(true) => {}
+                Err(e) => slog::debug!(self.logger, "Failed to probe streams of {}: {}", file, e),
+            }
+        }
+
+        false
+    }
+
+    /// Decodes `window` of the first audio stream of `file` and fingerprints it with
+    /// [`audio::fingerprint`].
+    async fn extract_audio(&mut self, file: &str, window: Window) -> Result<Vec<Frame>> {
+        if !is_url(file) && !std::path::Path::new(file).exists() {
+            return Err(SectionizerError::FileNotFound(file.to_string()));
+        }
+
+        let (tt, sseof) = self.bounds(file, window).await?;
+        let args = audio::ffmpeg_args(file, tt, sseof, self.config.audio_mix);
+        let (mut session, stream) = self.spawn_direct(file, args)?;
+
+        let samples = audio::read_samples(stream).await;
+        self.check_extraction(&mut session, file, samples.len())
+            .await?;
+
+        let mut frames = audio::fingerprint(&samples, self.config.audio_freq_range);
+        if let Some(limit) = self.frame_limit(window, audio::FPS) {
+            frames.retain(|x| x.idx < limit);
+        }
+
+        slog::debug!(self.logger, "Fingerprinted audio";
+            "file" => file,
+            "samples" => samples.len(),
+            "fingerprints" => frames.len(),
+        );
+
+        Ok(frames)
+    }
+
+    /// Matches the audio of `file1` and `file2` rather than their video. Audio is fingerprinted
+    /// at `audio::FPS` and matched with `audio_max_dist`, going through the same grouping and
+    /// post-processing as video frames otherwise.
+    async fn run_audio(
+        &mut self,
+        file1: String,
+        file2: String,
+        windows: (Window, Window),
+    ) -> Result<(Sections, Sections)> {
+        let (window1, window2) = self.windows(&file1, &file2, windows).await;
+
+        let frames1 = self.extract_audio(&file1, window1).await?;
+        let frames2 = self.extract_audio(&file2, window2).await?;
+
+        // the video specific knobs make no sense for audio fingerprints.
+        let mut sectionizer = self.clone();
+        sectionizer.config.hash_max_dist = self.config.audio_max_dist;
+        sectionizer.config.auto_threshold = false;
+        sectionizer.config.threshold_sweep = None;
+        sectionizer.config.hash_stride = 1;
+        sectionizer.config.granularity = Granularity::Frame;
+        sectionizer.config.similarity = Similarity::Hash;
+        sectionizer.config.precise_boundaries = false;
+
+        let (sections1, sections2) = sectionizer
+            .categorize_frames_concurrently(frames1, frames2, audio::FPS, audio::FPS)
+            .await;
+
+        Ok((
+            Sections {
+                window_start: self.window_start(&file1, window1).await,
+                window: Some(window1),
+                reverse: windows.0.is_tail(),
+                target: file1,
+                ..sections1
+            },
+            Sections {
+                window_start: self.window_start(&file2, window2).await,
+                window: Some(window2),
+                reverse: windows.1.is_tail(),
+                target: file2,
+                ..sections2
+            },
+        ))
+    }
+
+    /// Returns the filter `file` has to be deinterlaced with, if any.
+    async fn deinterlace_filter(&self, file: &str) -> Option<DeinterlaceFilter> {
+        match self.config.deinterlace {
+            Deinterlace::Off => None,
+            Deinterlace::Always(filter) => Some(filter),
+            Deinterlace::Auto(filter) => {
+                match probe::interlaced(&self.config.ffprobe, file).await {
+                    Ok(true) => {
+                        slog::debug!(self.logger, "Deinterlacing";
+                            "file" => file,
+                            "filter" => filter.name(),
+                        );
+                        Some(filter)
+                    }
+                    Ok(false) => None,
+                    Err(e) => {
+                        slog::warn!(
+                            self.logger,
+                            "Failed to probe field order of {}: {}",
+                            file,
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns whether the frames of `file` should be tonemapped to SDR, see `tonemap`. Files which
+    /// can't be probed are assumed to be SDR.
+    async fn tonemap(&self, file: &str) -> bool {
+        match self.config.tonemap {
+            Tonemap::Off => false,
+            Tonemap::Always => true,
+            Tonemap::Auto => match probe::hdr(&self.config.ffprobe, file).await {
+                Ok(hdr) => {
+                    if hdr {
+                        slog::debug!(self.logger, "Tonemapping"; "file" => file);
+                    }
+                    hdr
+                }
+                Err(e) => {
+                    slog::warn!(
+                        self.logger,
+                        "Failed to probe color transfer of {}: {}",
+                        file,
+                        e
+                    );
+                    false
+                }
+            },
+        }
+    }
+
+    /// Stops ffmpeg for `session`, for when the rest of its frames are not needed.
+    async fn kill(&mut self, session: Session) {
+        match session {
+            Session::Managed(id) => {
+                if let Some(state) = self.state.as_mut() {
+                    let _ = state.die(id).await;
+                }
+            }
+            Session::Direct(mut child) => {
+                let _ = child.kill().await;
+            }
+        }
+    }
+
+    /// Logs whatever ffmpeg printed to stderr for `session` once its frames have been read,
+    /// and turns a session which produced no frames at all into an error carrying that output.
+    async fn check_extraction(
+        &mut self,
+        session: &mut Session,
+        file: &str,
+        decoded: usize,
+    ) -> Result<()> {
+        let stderr = match (session, self.state.as_mut()) {
+            (Session::Managed(id), Some(state)) => {
+                state.get_stderr(id.clone()).await.unwrap_or_default()
+            }
+            (Session::Managed(_), None) => String::new(),
+            // stderr is only read to its end once ffmpeg gave up, on success it may still be
+            // running.
+            (Session::Direct(child), _) => match child.stderr.as_mut() {
+                Some(pipe) if decoded == 0 => {
+                    let mut stderr = String::new();
+                    let _ = pipe.read_to_string(&mut stderr).await;
+                    stderr
+                }
+                _ => String::new(),
+            },
+        };
+
+        if !stderr.trim().is_empty() {
+            slog::debug!(self.logger, "ffmpeg stderr"; "file" => file, "stderr" => &stderr);
+        }
+
+        if decoded == 0 {
+            return Err(SectionizerError::ExtractionFailed(file.to_string(), stderr));
+        }
+
+        Ok(())
+    }
+
+    fn log_extracted(&self, file: &str, hashed: usize, ignored: IgnoredFrames) {
+        slog::debug!(self.logger, "Extracted frames";
+            "file" => file,
+            "decoded" => hashed + ignored.total(),
+            "hashed" => hashed,
+            "black" => ignored.black,
+            "solid" => ignored.solid,
+            "strided" => ignored.strided,
+        );
+    }
+
+    /// Returns the frame rate used for `file`: `explicit` if the caller gave one, otherwise the
+    /// probed rate if `detect_fps` is set, otherwise the configured `fps`.
+    async fn fps(&self, file: &str, explicit: Option<f64>) -> f64 {
+        if let Some(fps) = explicit {
+            return fps;
+        }
+
+        if !self.config.detect_fps {
+            return self.config.fps;
+        }
+
+        match probe::frame_rate(&self.config.ffprobe, file).await {
+            Ok(fps) => fps,
+            Err(e) => {
+                slog::warn!(
+                    self.logger,
+                    "Failed to probe frame rate of {}, assuming {}: {}",
+                    file,
+                    self.config.fps,
+                    e
+                );
+                self.config.fps
+            }
+        }
+    }
+
+    /// Returns the second at which `window` of `file` starts. For tail windows this requires
+    /// probing the duration of the file, if that fails we fall back to `0` and warn.
+    async fn window_start(&self, file: &str, window: Window) -> u128 {
+        let secs = match window {
+            Window::Head(_) | Window::Full => return 0,
+            Window::Span { start, .. } => return start as u128,
+            Window::Tail(secs) => secs,
+        };
+
+        match probe::duration(&self.config.ffprobe, file).await {
+            Ok(duration) => (duration as u128).saturating_sub(secs as u128),
+            Err(e) => {
+                slog::warn!(
+                    self.logger,
+                    "Failed to probe duration of {}, timestamps will be relative: {}",
+                    file,
+                    e
+                );
+                0
+            }
+        }
+    }
+
+    /// Method `matched_timestamps` returns the timestamps, in seconds, of every frame of `frames`
+    /// which matches a frame of `reference`. This is the raw signal sections are built from,
+    /// before matches are bucketed and merged, which makes it suitable for drawing match heatmaps.
+    pub fn matched_timestamps(
+        &self,
+        frames: Vec<Frame>,
+        reference: Vec<Frame>,
+        fps: f64,
+    ) -> Vec<f64> {
+        let frames = self.aggregate(frames, fps);
+        let reference = self.aggregate(reference, fps);
+
+        let params = self.params(self.threshold(&frames, &reference, fps), fps);
+        let indextree = self.tree_from_vec(reference);
+
+        self.matches(&indextree, frames, params)
+            .into_iter()
+            .map(|x| frame_to_secs(x.0.idx, fps))
+            .collect()
+    }
+
+    /// Returns the hash distance threshold to use when matching `frames1` against `frames2`.
+    fn threshold(&self, frames1: &[Frame], frames2: &[Frame], fps: f64) -> isize {
+        if let Some(dists) = self.config.threshold_sweep.clone() {
+            self.sweep_threshold(frames1, frames2, fps, dists)
+        } else if self.config.auto_threshold {
+            self.auto_threshold(frames1, frames2)
+        } else {
+            self.config.hash_max_dist
+        }
+    }
+
+    /// Method `sweep_threshold` picks a hash distance threshold for a pair of frame vectors by
+    /// matching them at every distance of `dists`.
+    ///
+    /// At a good threshold the section found hardly moves when the threshold is nudged either way,
+    /// while at a bad one it is still growing into unrelated content or falling apart. The longest
+    /// section of `frames1` is therefore found at every distance, and the distance whose section
+    /// boundaries change least compared to its neighbouring distances is picked. Ties go to the
+    /// lower distance.
+    ///
+    /// # Returns
+    /// The chosen threshold, or the configured `hash_max_dist` if no distance finds a section.
+    pub fn sweep_threshold(
+        &self,
+        frames1: &[Frame],
+        frames2: &[Frame],
+        fps: f64,
+        dists: RangeInclusive<isize>,
+    ) -> isize {
+        let tree = self.tree_from_vec(frames2.to_vec());
+        let dists = dists.collect::<Vec<_>>();
+
+        let bounds = dists
+            .iter()
+            .map(|dist| {
+                self.get_groups(&tree, frames1.to_vec(), self.params(*dist, fps))
+                    .groups
+                    .into_iter()
+                    .filter_map(Section::new)
+                    .map(|x| (x.first, x.last))
+                    .max_by_key(|(first, last)| last - first)
+            })
+            .collect::<Vec<_>>();
+
+        // a section appearing or vanishing between two distances is as unstable as it gets.
+        let change = |a: Option<(u64, u64)>, b: Option<(u64, u64)>| match (a, b) {
+            (Some(a), Some(b)) => a.0.abs_diff(b.0) + a.1.abs_diff(b.1),
+            _ => u64::MAX,
+        };
+
+        (0..dists.len())
+            .filter(|i| bounds[*i].is_some())
+            .min_by_key(|i| {
+                let prev = i.checked_sub(1).map(|j| change(bounds[j], bounds[*i]));
+                let next = bounds.get(i + 1).map(|x| change(bounds[*i], *x));
+                prev.into_iter().chain(next).max().unwrap_or(0)
+            })
+            .map(|i| dists[i])
+            .unwrap_or(self.config.hash_max_dist)
+    }
+
+    /// Method `auto_threshold` picks a hash distance threshold for a pair of frame vectors.
+    ///
+    /// Every frame of `frames1` is matched against its nearest neighbour in `frames2`. Frames
+    /// belonging to a shared scene cluster around very small distances while unrelated content
+    /// lands much further away, so the threshold is placed at the split that best separates the
+    /// two clusters of the resulting histogram (Otsu's method).
+    ///
+    /// # Returns
+    /// The chosen threshold, or the configured `hash_max_dist` if there is nothing to compare.
+    pub fn auto_threshold(&self, frames1: &[Frame], frames2: &[Frame]) -> isize {
+        let tree = self.tree_from_vec(frames2.to_vec());
+
+        // frames without a neighbour under the ceiling are counted in the last bucket.
+        let mut histogram = [0usize; AUTO_THRESHOLD_CEIL as usize + 1];
+        for frame in frames1 {
+            let nearest = tree
+                .find(*frame, AUTO_THRESHOLD_CEIL)
+                .into_iter()
+                .map(|(_, dist)| dist)
+                .min()
+                .unwrap_or(AUTO_THRESHOLD_CEIL);
+
+            histogram[nearest as usize] += 1;
+        }
+
+        let total = histogram.iter().sum::<usize>() as f64;
+        let weighted_total = histogram
+            .iter()
+            .enumerate()
+            .map(|(dist, count)| (dist * count) as f64)
+            .sum::<f64>();
+
+        let mut threshold = self.config.hash_max_dist;
+        let mut best_variance = 0.0;
+
+        let mut below = 0.0;
+        let mut weighted_below = 0.0;
+
+        for (dist, count) in histogram.iter().enumerate() {
+            below += *count as f64;
+            weighted_below += (dist * count) as f64;
+
+            let above = total - below;
+            if below == 0.0 || above == 0.0 {
+                continue;
+            }
+
+            let mean_below = weighted_below / below;
+            let mean_above = (weighted_total - weighted_below) / above;
+            let variance = below * above * (mean_below - mean_above).powi(2);
+
+            if variance > best_variance {
+                best_variance = variance;
+                threshold = dist as isize;
+            }
+        }
+
+        threshold
+    }
+
+    /// Pairs every frame of `framevec` with its closest match from `indextree`, dropping frames
+    /// which have no match.
+    fn matches(
+        &self,
+        indextree: &BkTree<Frame>,
+        framevec: Vec<Frame>,
+        params: MatchParams,
+    ) -> Vec<MatchedFrames> {
+        framevec
+            .into_iter()
+            .filter_map(|x| {
+                self.nearest(indextree, x, params.max_dist)
+                    .into_iter()
+                    .find_map(|(y, dist)| {
+                        let idx = y.partner_idx(x.idx, params.min_separation)?;
+                        Some(MatchedFrames(x, Frame { idx, run: 0, ..y }, dist))
+                    })
+            })
+            .collect()
+    }
+
+    /// Method `sections_as_frames` matches `frames` against `reference` like
+    /// [`categorize_frames`](Self::categorize_frames) does, but returns the sections of `frames`
+    /// as the indices of their first and last matched frame instead of seconds.
+    pub fn sections_as_frames(
+        &self,
+        frames: Vec<Frame>,
+        reference: Vec<Frame>,
+        fps: f64,
+    ) -> Vec<(u64, u64)> {
+        let frames = self.aggregate(frames, fps);
+        let reference = self.aggregate(reference, fps);
+
+        let params = self.params(self.threshold(&frames, &reference, fps), fps);
+        let indextree = self.tree_from_vec(reference);
+
+        self.get_groups(&indextree, frames, params)
+            .groups
+            .into_iter()
+            .filter_map(|group| {
+                let frames = group.iter().flat_map(|(_, x)| x.iter().map(|x| x.idx));
+                Some((frames.clone().min()?, frames.max()?))
+            })
+            .collect()
+    }
+
+    /// Method `inspect_groups` matches `frames` against `reference` like
+    /// [`sections_as_frames`](Self::sections_as_frames) does, but returns every bucket which
+    /// received a match, before buckets are filtered and grouped into sections. This is meant for
+    /// tuning thresholds against content which is detected wrongly.
+    pub fn inspect_groups(
+        &self,
+        frames: Vec<Frame>,
+        reference: Vec<Frame>,
+        fps: f64,
+    ) -> Vec<BucketInfo> {
+        let mut sectionizer = self.clone();
+        sectionizer.config.debug_groups = true;
+
+        let frames = sectionizer.aggregate(frames, fps);
+        let reference = sectionizer.aggregate(reference, fps);
+
+        let params = sectionizer.params(sectionizer.threshold(&frames, &reference, fps), fps);
+        let indextree = sectionizer.tree_from_vec(reference);
+
+        sectionizer.get_groups(&indextree, frames, params).buckets
+    }
+
+    /// Finds the sections of `framevec` in `indextree`, see [`detect`](Self::detect).
+    ///
+    /// # Returns
+    /// The sections in seconds along with the quality of the detection.
+    fn get_sections(
+        &self,
+        indextree: BkTree<Frame>,
+        framevec: Vec<Frame>,
+        params: MatchParams,
+    ) -> (Vec<(u128, u128)>, Quality) {
+        self.seconds(self.detect(indextree, framevec, params))
+    }
+
+    /// Method `repair_frame_order` returns `frames` sorted by index, keeping only the first of
+    /// several frames sharing an index. Sections are computed from the differences between the
+    /// indices of neighbouring frames, which only make sense in order.
+    ///
+    /// Frames come out of ffmpeg in order, so needing to reorder them points at a bug upstream and
+    /// is logged.
+    pub fn repair_frame_order(&self, mut frames: Vec<Frame>) -> Vec<Frame> {
+        if frames.windows(2).all(|x| x[0].idx < x[1].idx) {
+            return frames;
+        }
+
+        let len = frames.len();
+        frames.sort_by_key(|x| x.idx);
+        frames.dedup_by_key(|x| x.idx);
+
+        slog::warn!(
+            self.logger,
+            "Frames out of order, reordered them and dropped {} duplicates",
+            len - frames.len()
+        );
+
+        frames
+    }
+
+    /// Matches `framevec` against `indextree` and runs the resulting sections through the
+    /// `post_processors`.
+    fn detect(
+        &self,
+        indextree: BkTree<Frame>,
+        framevec: Vec<Frame>,
+        params: MatchParams,
+    ) -> Detection {
+        let framevec = self.repair_frame_order(framevec);
+        let frames = framevec.clone();
+        let ctx = Ctx {
+            config: &self.config,
+            frames: &frames,
+            fps: params.fps,
+            hashed_fps: self.hashed_fps(params.fps),
+            bucket_secs: params.bucket_secs,
+        };
+
+        let grouping = self.get_groups(&indextree, framevec, params);
+        let candidates = grouping
+            .groups
+            .into_iter()
+            .filter_map(Section::new)
+            .collect::<Vec<_>>();
+        let candidate_count = candidates.len();
+
+        let sections = self
+            .config
+            .post_processors
+            .iter()
+            .fold(candidates, |sections, step| step.process(sections, &ctx));
+
+        Detection {
+            rejected: grouping.rejected + candidate_count.saturating_sub(sections.len()),
+            sections,
+            frames,
+            distances: grouping.distances,
+            partners: grouping.partners,
+            params,
+        }
+    }
+
+    /// Converts the sections of `detection` to seconds.
+    ///
+    /// # Returns
+    /// The sections in seconds along with the quality of the detection.
+    fn seconds(&self, detection: Detection) -> (Vec<(u128, u128)>, Quality) {
+        let fps = detection.params.fps;
+        let quality = Quality::new(
+            &detection.sections,
+            &detection.distances,
+            &detection.frames,
+            detection.params.max_dist,
+            detection.rejected,
+            self.config.section_score,
+        );
+
+        let sections = detection
+            .sections
+            .iter()
+            .map(|x| self.section_secs(x, fps))
+            .collect::<Vec<_>>();
+
+        (sections, quality)
+    }
+
+    /// Returns the bounds of `section` in seconds, rounded as configured. The end is exclusive,
+    /// the time at which the frame following `last` is shown, so a section of a single frame
+    /// still has a width.
+    fn section_secs(&self, section: &Section, fps: f64) -> (u128, u128) {
+        (
+            self.config
+                .start_rounding
+                .apply(frame_to_secs(section.first, fps)),
+            self.config
+                .end_rounding
+                .apply(frame_to_secs(section.last + 1, fps)),
+        )
+    }
+
+    /// Drops the sections of both detections which the other one doesn't corroborate, when
+    /// `cross_validate` is set. A section is corroborated if at least half of its matched frames
+    /// were matched with frames lying within a section of the other file.
+    fn cross_validate(&self, mut a: Detection, mut b: Detection) -> (Detection, Detection) {
+        if !self.config.cross_validate {
+            return (a, b);
+        }
+
+        let corroborated = |section: &Section, this: &Detection, other: &Detection| {
+            let matched = section.buckets.iter().flat_map(|(_, x)| x.iter());
+            let total = matched.clone().count();
+            let hits = matched
+                .filter_map(|x| this.partners.get(&x.idx))
+                .filter(|idx| {
+                    other
+                        .sections
+                        .iter()
+                        .any(|x| **idx >= x.first && **idx <= x.last)
+                })
+                .count();
+
+            hits as f64 >= total as f64 * CROSS_VALIDATION_MIN_RATIO
+        };
+
+        let keep_a = a
+            .sections
+            .iter()
+            .map(|x| corroborated(x, &a, &b))
+            .collect::<Vec<_>>();
+        let keep_b = b
+            .sections
+            .iter()
+            .map(|x| corroborated(x, &b, &a))
+            .collect::<Vec<_>>();
+
+        for (detection, keep) in [(&mut a, keep_a), (&mut b, keep_b)] {
+            let before = detection.sections.len();
+            let mut keep = keep.into_iter();
+            detection.sections.retain(|_| keep.next().unwrap_or(false));
+            detection.rejected += before - detection.sections.len();
+        }
+
+        (a, b)
+    }
+
+    /// Matches `framevec` against `indextree`, buckets the matched frames and groups nearby
+    /// buckets together. Every returned group makes up one section, its buckets are sorted.
+    fn get_groups(
+        &self,
+        indextree: &BkTree<Frame>,
+        framevec: Vec<Frame>,
+        params: MatchParams,
+    ) -> Grouping {
+        // not enough frames to fill a single bucket, so there is nothing to find here.
+        if framevec.len() < params.min_bucket_matches {
+            return Grouping {
+                groups: Vec::new(),
+                distances: BTreeMap::new(),
+                rejected: 0,
+                partners: BTreeMap::new(),
+                buckets: Vec::new(),
+            };
+        }
+
+        let started = Instant::now();
+        let mut framevec = self.matches(indextree, framevec, params);
+        self.stats.add_comparison(started.elapsed());
+
+        // sort framevec to avoid overflow
+        framevec.sort_by_key(|x| x.0.idx);
+
+        // a BTreeMap keeps the buckets ordered, so identical inputs always group identically.
+        let mut groups: BTreeMap<u64, Vec<Frame>> = BTreeMap::new();
+
+        let mut distances = BTreeMap::new();
+        let mut partners = BTreeMap::new();
+
+        let bucket_frames = params.fps * params.bucket_secs;
+
+        for frame in framevec {
+            let bucket = (frame.0.idx as f64 / bucket_frames) as u64;
+            groups.entry(bucket).or_default().push(frame.0);
+            distances.insert(frame.0.idx, frame.2);
+            partners.insert(frame.0.idx, frame.1.idx);
+        }
+
+        let mut buckets = if !self.config.debug_groups {
+            Vec::new()
+        } else {
+            groups
+                .iter()
+                .map(|(bucket, frames)| {
+                    let mut distances = frames
+                        .iter()
+                        .filter_map(|x| distances.get(&x.idx).copied())
+                        .collect::<Vec<_>>();
+                    distances.sort_unstable();
+
+                    BucketInfo {
+                        bucket: *bucket,
+                        start_secs: *bucket as f64 * params.bucket_secs,
+                        matches: frames.len(),
+                        distances,
+                        group: None,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut groups = groups
+            .into_iter()
+            .filter(|(_, x)| x.len() >= params.min_bucket_matches)
+            .collect::<Vec<_>>();
+
+        let (groups, rejected): (Vec<_>, Vec<_>) = groups
+            .group_by_mut(|(a, _), (b, _)| b - a <= params.merge_gap)
+            .map(|x| {
+                x.sort_by_key(|(a, _)| *a);
+                x.to_vec()
+            })
+            .partition(|x| {
+                x.iter().map(|(_, x)| x.len()).sum::<usize>() >= self.config.min_total_matches
+            });
+
+        for info in buckets.iter_mut() {
+            info.group = groups
+                .iter()
+                .position(|x| x.iter().any(|(bucket, _)| *bucket == info.bucket));
+
+            slog::debug!(self.logger, "Bucket";
+                "bucket" => info.bucket,
+                "start" => info.start_secs,
+                "matches" => info.matches,
+                "distances" => ?info.distances,
+                "group" => ?info.group,
+            );
+        }
+
+        Grouping {
+            groups,
+            distances,
+            rejected: rejected.len(),
+            partners,
+            buckets,
+        }
+    }
+
+    /// Decodes and hashes every frame from `stream`, or only the first `limit` frames if set,
+    /// calling `progress` with the number of frames read so far every `PROGRESS_INTERVAL` frames.
+    /// At the same interval `stop` is handed the frames hashed so far, decoding stops as soon as
+    /// it returns `true`.
+    async fn compute_frame_vec(
+        &self,
+        file: &str,
+        stream: impl AsyncRead + Unpin,
+        limit: Option<u64>,
+        progress: impl Fn(u64),
+        stop: impl Fn(&[Frame]) -> bool,
+    ) -> (Vec<Frame>, IgnoredFrames) {
+        let mut frames = Vec::with_capacity(240 * 24);
+        let (width, height) = self.config.frame_size;
+        let mut buf = vec![0u8; width as usize * height as usize * 3];
+
+        // frames straddling the end of the buffer are completed by `read_exact` on the next fill,
+        // so buffering never misaligns them.
+        let mut stream = BufReader::with_capacity(self.config.read_buffer_size, stream);
+
+        let hasher = self.hasher();
+        let debug_dir = self.debug_dir(file);
+
+        let mut idx = 0u64;
+        let mut ignored = IgnoredFrames::default();
+
+        while stream.read_exact(buf.as_mut()).await.is_ok() {
+            let raw: &[u8] = buf.as_ref();
+            self.stats.add_frame(raw.len());
+
+            if idx.is_multiple_of(PROGRESS_INTERVAL) && idx != 0 {
+                progress(idx);
+
+                if stop(&frames) {
+                    slog::debug!(self.logger, "Stopping early"; "file" => file, "frames" => idx);
+                    break;
+                }
+            }
+
+            if limit.is_some_and(|x| idx >= x) {
+                break;
+            }
+
+            if idx >= self.config.max_frames {
+                slog::warn!(
+                    self.logger,
+                    "Reached the limit of {} frames, ignoring the rest of the stream",
+                    self.config.max_frames
+                );
+                break;
+            }
+
+            if !idx.is_multiple_of(self.config.hash_stride.max(1)) {
+                ignored.count(IgnoreReason::Stride);
+                idx += 1;
+                continue;
+            }
+
+            if self.config.skip_solid_frames {
+                let thresholds = (
+                    self.config.solid_frame_threshold,
+                    self.config.black_luma_threshold,
+                );
+                if let Some(reason) = IgnoreReason::classify(raw, thresholds) {
+                    ignored.count(reason);
+                    idx += 1;
+                    continue;
+                }
+            }
+
+            // `check_profile` made sure ffmpeg emits packed rgb24, which is the layout of `RgbImage`.
+            let frame = image::RgbImage::from_raw(width, height, raw.to_vec()).unwrap();
+            let frame = self.crop(frame);
+
+            // the histogram needs the colors, so it is taken before the grayscale conversion.
+            let histogram = match self.config.similarity {
+                Similarity::Hash => None,
+                _ => Some(Histogram::new(&frame)),
+            };
+
+            let frame = self.to_luma(frame);
+
+            let hash = hasher(&frame);
+
+            if let Some(dir) = debug_dir
+                .as_ref()
+                .filter(|_| idx < self.config.debug_frames)
+            {
+                let path = dir.join(format!("{:06}_{}.png", idx, hash));
+                if let Err(e) = frame.save(&path) {
+                    slog::warn!(self.logger, "Failed to write debug frame {:?}: {}", path, e);
+                }
+            }
+
+            let frame = Frame {
+                hash,
+                idx,
+                histogram,
+                run: 0,
+            };
+            frames.push(frame);
+            idx += 1;
+        }
+
+        (frames, ignored)
+    }
+
+    /// Creates and returns the directory debug frames of `file` are written to, if enabled.
+    fn debug_dir(&self, file: &str) -> Option<std::path::PathBuf> {
+        let name = std::path::Path::new(file).file_name()?;
+        let dir = std::path::Path::new(self.config.debug_frames_dir.as_ref()?).join(name);
+
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            slog::warn!(
+                self.logger,
+                "Failed to create debug frame directory {:?}: {}",
+                dir,
+                e
+            );
+            return None;
+        }
+
+        Some(dir)
+    }
+
+    /// Returns a function hashing a frame with the configured `hasher`, or into a hash of the
+    /// configured `hash_width` if there is none.
+    fn hasher(&self) -> Box<dyn Fn(&image::RgbImage) -> FrameHash> {
+        if let Some(hasher) = self.config.hasher.clone() {
+            return Box::new(move |x| hasher.hash(x));
+        }
+
+        let width = self.config.hash_width;
+
+        match width {
+            HashWidth::Bits64 => {
+                let hasher = self.hasher_config::<[u8; 8]>().to_hasher();
+                Box::new(move |x| FrameHash::from_bytes(width, hasher.hash_image(x).as_bytes()))
+            }
+            HashWidth::Bits128 => {
+                let hasher = self.hasher_config::<[u8; 16]>().to_hasher();
+                Box::new(move |x| FrameHash::from_bytes(width, hasher.hash_image(x).as_bytes()))
+            }
+            HashWidth::Bits256 => {
+                let hasher = self.hasher_config::<[u8; 32]>().to_hasher();
+                Box::new(move |x| FrameHash::from_bytes(width, hasher.hash_image(x).as_bytes()))
+            }
+        }
+    }
+
+    fn hasher_config<B: img_hash::HashBytes>(&self) -> img_hash::HasherConfig<B> {
+        let (w, h) = self.config.hash_width.hash_size();
+
+        img_hash::HasherConfig::with_bytes_type::<B>()
+            .hash_size(w, h)
+            .hash_alg(self.config.hash_alg)
+            .resize_filter(self.config.resize_filter)
+            .preproc_dct()
+    }
+
+    /// Crops `crop_margin` off every edge of `frame` so that only its center gets hashed.
+    fn crop(&self, frame: image::RgbImage) -> image::RgbImage {
+        if self.config.crop_margin <= 0.0 {
+            return frame;
+        }
+
+        let margin = self.config.crop_margin.min(0.45);
+        let x = (frame.width() as f32 * margin).round() as u32;
+        let y = (frame.height() as f32 * margin).round() as u32;
+
+        image::imageops::crop_imm(&frame, x, y, frame.width() - 2 * x, frame.height() - 2 * y)
+            .to_image()
+    }
+
+    /// Returns the groups of buckets of `frames`, the frames of a file decoded so far, matching
+    /// `tree` which can no longer grow, because more than `merge_gap_secs` have been decoded since
+    /// their end.
+    fn finished_groups(
+        &self,
+        tree: &BkTree<Frame>,
+        frames: &[Frame],
+        fps: f64,
+    ) -> Vec<Vec<Bucket>> {
+        let current = match frames.last() {
+            Some(x) => x.idx,
+            None => return Vec::new(),
+        };
+
+        let params = self.params(self.config.hash_max_dist, fps);
+        let current = (current as f64 / (fps * params.bucket_secs)) as u64;
+        let frames = self.aggregate(frames.to_vec(), fps);
+
+        self.get_groups(tree, frames, params)
+            .groups
+            .into_iter()
+            .filter(|x| matches!(x.last(), Some((last, _)) if current > last + params.merge_gap))
+            .collect()
+    }
+
+    /// Returns whether any of the `finished` groups of buckets satisfies `early_stop`.
+    fn section_confirmed(&self, finished: &[Vec<Bucket>], early_stop: EarlyStop) -> bool {
+        finished.iter().any(|group| {
+            let (first, last) = match (group.first(), group.last()) {
+                (Some(first), Some(last)) => (first.0, last.0),
+                _ => return false,
+            };
+
+            let span = last - first + 1;
+            span as f64 * self.config.bucket_secs >= early_stop.min_secs
+                && group.len() as f64 / span as f64 >= early_stop.min_density
+        })
+    }
+
+    /// Calls `on_section` with every group of `finished` which wasn't yet, as recorded by the
+    /// first bucket of each group in `reported`.
+    fn report_sections(
+        &self,
+        finished: &[Vec<Bucket>],
+        frames: &[Frame],
+        fps: f64,
+        reported: &RefCell<BTreeSet<u64>>,
+        on_section: &dyn Fn(DetectedSection),
+    ) {
+        for group in finished {
+            let key = match group.first() {
+                Some((key, _)) => *key,
+                None => continue,
+            };
+
+            if !reported.borrow_mut().insert(key) {
+                continue;
+            }
+
+            let section = match Section::new(group.clone()) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let in_range = |idx: u64| idx >= section.first && idx <= section.last;
+            let matched = group.iter().map(|(_, x)| x.len()).sum::<usize>();
+            let total = frames.iter().filter(|x| in_range(x.idx)).count();
+
+            on_section(DetectedSection {
+                section: self.section_secs(&section, fps),
+                confidence: (matched as f64 / total.max(1) as f64).min(1.0),
+            });
+        }
+    }
+
+    /// Converts `frame` to grayscale with the configured `luma_weights`, if any. The result is
+    /// kept as RGB with all three channels equal so that the conversion `img_hash` does
+    /// afterwards leaves it untouched.
+    fn to_luma(&self, mut frame: image::RgbImage) -> image::RgbImage {
+        let [r, g, b] = match self.config.luma_weights {
+            Some(x) => x,
+            None => return frame,
+        };
+
+        let total = r + g + b;
+        if total <= 0.0 {
+            return frame;
+        }
+
+        for px in frame.pixels_mut() {
+            let [pr, pg, pb] = px.0;
+            let luma = (pr as f32 * r + pg as f32 * g + pb as f32 * b) / total;
+            px.0 = [luma.round().min(255.0) as u8; 3];
+        }
+
+        frame
+    }
+
+    /// Method `nearest` returns the frames of `tree` within `max_dist` of `frame` along with
+    /// their distance, closest first. This is the primitive frames are matched with.
+    ///
+    /// Frames exactly `max_dist` away are included, or excluded with an exclusive
+    /// `distance_bound`.
+    pub fn nearest(
+        &self,
+        tree: &BkTree<Frame>,
+        frame: Frame,
+        max_dist: isize,
+    ) -> Vec<(Frame, isize)> {
+        // `BkTree::find` is inclusive, distances are integers so exclusive is one less.
+        let max_dist = match self.config.distance_bound {
+            DistanceBound::Inclusive => max_dist,
+            DistanceBound::Exclusive => max_dist - 1,
+        };
+
+        if max_dist < 0 {
+            return Vec::new();
+        }
+
+        let mut nearest = tree
+            .find(frame, max_dist)
+            .into_iter()
+            .map(|(x, dist)| (*x, dist))
+            .collect::<Vec<_>>();
+
+        nearest.sort_by_key(|(x, dist)| (*dist, x.idx));
+
+        nearest
+    }
+
+    /// Collapses every run of consecutive `frames` with the same hash and histogram into its
+    /// first frame, which keeps the index of the last one in [`Frame::last_idx`]. Frames are
+    /// consecutive if no more than `hash_stride` apart, as frames dropped before matching
+    /// interrupt a run.
+    fn dedupe(&self, mut frames: Vec<Frame>) -> Vec<Frame> {
+        frames.sort_by_key(|x| x.idx);
+
+        let stride = self.config.hash_stride.max(1);
+        let mut deduped: Vec<Frame> = Vec::with_capacity(frames.len());
+
+        for frame in frames {
+            match deduped.last_mut() {
+                Some(last)
+                    if last.hash == frame.hash
+                        && last.histogram == frame.histogram
+                        && frame.idx <= last.last_idx() + stride =>
+                {
+                    last.run = frame.last_idx() - last.idx;
+                }
+                _ => deduped.push(frame),
+            }
+        }
+
+        deduped
+    }
+
+    /// Method `tree_from_vec` indexes `frames` for matching, see [`nearest`](Self::nearest).
+    pub fn tree_from_vec(&self, frames: Vec<Frame>) -> BkTree<Frame> {
+        let similarity = self.config.similarity;
+        let mut tree = BkTree::new(move |a: &Frame, b: &Frame| distance(similarity, a, b));
+
+        let frames = if self.config.dedupe_tree_frames {
+            self.dedupe(frames)
+        } else {
+            frames
+        };
+
+        match self.config.max_tree_frames {
+            Some(max) if frames.len() > max => {
+                let step = frames.len().div_ceil(max.max(1));
+                tree.insert_all(frames.into_iter().step_by(step));
+            }
+            _ => tree.insert_all(frames),
+        }
+
+        tree
+    }
+}
+
+/// Reason for which a frame was dropped before matching.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IgnoreReason {
+    /// The frame is entirely black.
+    Black,
+    /// The frame is a single solid color other than black.
+    Solid,
+    /// The frame was skipped by `hash_stride`.
+    Stride,
+}
+
+impl IgnoreReason {
+    /// Classifies a raw RGB frame, returning `None` if the frame should be kept. Every channel of
+    /// every pixel may differ from the first pixel by up to `solid` for the frame to be solid, and
+    /// the Rec. 601 luma of every pixel may not exceed `black` for it to be black.
+    pub fn classify(raw: &[u8], (solid, black): (u8, u8)) -> Option<Self> {
+        let first = raw.chunks_exact(3).next()?;
+        let close = |a: u8, b: u8| (a as i16 - b as i16).abs() <= solid as i16;
+        let luma = |px: &[u8]| 0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32;
+
+        if !raw
+            .chunks_exact(3)
+            .all(|px| px.iter().zip(first).all(|(a, b)| close(*a, *b)))
+        {
+            return None;
+        }
+
+        if raw.chunks_exact(3).all(|px| luma(px) <= black as f32) {
+            Some(Self::Black)
+        } else {
+            Some(Self::Solid)
+        }
+    }
+}
+
+/// Number of frames of a file that were dropped before matching, broken down by reason.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct IgnoredFrames {
+    pub black: usize,
+    pub solid: usize,
+    #[serde(default)]
+    pub strided: usize,
+}
+
+impl IgnoredFrames {
+    fn count(&mut self, reason: IgnoreReason) {
+        match reason {
+            IgnoreReason::Black => self.black += 1,
+            IgnoreReason::Solid => self.solid += 1,
+            IgnoreReason::Stride => self.strided += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.black + self.solid + self.strided
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Sections {
+    pub target: String,
+    /// Matched sections in seconds, relative to the start of the analyzed window, as half-open
+    /// `[start, end)` ranges.
+    pub sections: Vec<(u128, u128)>,
+    /// Second of the file at which the analyzed window starts, this is non-zero in reverse mode.
+    pub window_start: u128,
+    /// Frames that were dropped before matching.
+    pub ignored: IgnoredFrames,
+    /// Number of frames of the file which were hashed and matched.
+    pub analyzed: usize,
+    /// Whether `sections` being empty can be trusted.
+    pub status: AnalysisStatus,
+    /// Window of the file which was analyzed, `None` if the frames were not extracted by the
+    /// `Sectionizer` itself.
+    pub window: Option<Window>,
+    /// Whether the tail of the file was analyzed.
+    pub reverse: bool,
+    /// Frame rate used to map frames to seconds.
+    pub fps: f64,
+    /// How much `sections` can be trusted.
+    pub quality: Quality,
+    /// Fraction of the reference section each of `sections` spans, in the same order and at most
+    /// `1.0`. Only known when matching against a [`Fingerprint`], empty otherwise.
+    #[serde(default)]
+    pub coverage: Vec<f64>,
+}
+
+/// A bucket of matched frames as seen by [`Sectionizer::inspect_groups`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BucketInfo {
+    /// Index of the bucket, counted in `bucket_secs` from the start of the window.
+    pub bucket: u64,
+    /// Second of the window at which the bucket starts.
+    pub start_secs: f64,
+    /// Number of frames of the bucket which matched.
+    pub matches: usize,
+    /// Distances of those frames to their matches, sorted.
+    pub distances: Vec<isize>,
+    /// Index of the group of buckets this bucket ended up in, `None` if it was dropped for
+    /// having too few matches or its group was dropped.
+    pub group: Option<usize>,
+}
+
+/// Indicators of how much a detection can be trusted, for deciding whether to fall back to other
+/// sources of section data.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Quality {
+    /// Distance threshold the frames were matched with, as configured or as picked by
+    /// `auto_threshold` or `threshold_sweep`.
+    pub max_dist: isize,
+    /// Median distance between the frames of the sections and their matches, `None` if there
+    /// are no sections.
+    pub median_distance: Option<isize>,
+    /// Number of candidate sections dropped by `min_total_matches` or the `post_processors`.
+    pub rejected: usize,
+    /// Score between `0.0` and `1.0` of how likely the file really has a section in common with
+    /// the other file. This is the fraction of the frames of the longest section which matched,
+    /// scaled down the closer the median distance gets to the threshold.
+    pub confidence: f64,
+    /// Confidence of each section on its own, in the order of the sections. Computed like
+    /// `confidence` from the frames of that section, as scored by `section_score`.
+    #[serde(default)]
+    pub section_confidence: Vec<f64>,
+    /// Length in frames of the longest run of consecutive matched frames of each section, in the
+    /// order of the sections.
+    #[serde(default)]
+    pub section_runs: Vec<usize>,
+}
+
+impl Quality {
+    fn new(
+        sections: &[Section],
+        distances: &BTreeMap<u64, isize>,
+        frames: &[Frame],
+        max_dist: isize,
+        rejected: usize,
+        section_score: SectionScore,
+    ) -> Self {
+        let mut matched = sections
+            .iter()
+            .flat_map(|x| x.buckets.iter())
+            .flat_map(|(_, x)| x.iter())
+            .filter_map(|x| distances.get(&x.idx).copied())
+            .collect::<Vec<_>>();
+        matched.sort_unstable();
+
+        let median_distance = matched.get(matched.len().saturating_sub(1) / 2).copied();
+
+        let matched_fraction = |x: &Section| {
+            let in_range = |idx: u64| idx >= x.first && idx <= x.last;
+            let matched = match section_score {
+                SectionScore::Density => x
+                    .buckets
+                    .iter()
+                    .flat_map(|(_, x)| x.iter())
+                    .filter(|x| in_range(x.idx))
+                    .count(),
+                SectionScore::RunLength => postprocess::longest_run(x, frames),
+            };
+            let total = frames.iter().filter(|x| in_range(x.idx)).count();
+
+            matched as f64 / total.max(1) as f64
+        };
+
+        let closeness = match median_distance {
+            Some(median) => 1.0 - median as f64 / (max_dist + 1) as f64,
+            None => 0.0,
+        };
+        let score = |x: &Section| (matched_fraction(x) * closeness).clamp(0.0, 1.0);
+
+        let confidence = sections
+            .iter()
+            .max_by_key(|x| x.last - x.first)
+            .map_or(0.0, score);
+
+        Self {
+            max_dist,
+            median_distance,
+            rejected,
+            confidence,
+            section_confidence: sections.iter().map(score).collect(),
+            section_runs: sections
+                .iter()
+                .map(|x| postprocess::longest_run(x, frames))
+                .collect(),
+        }
+    }
+}
+
+/// Outcome of the analysis behind a [`Sections`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AnalysisStatus {
+    /// At least one section was found.
+    Found,
+    /// Both files were analyzed properly but they have no section in common.
+    NoneFound,
+    /// No section was found, but too few frames were analyzed in at least one of the files for
+    /// this to be meaningful, see `min_analyzed_secs`. Retrying with a larger window or a
+    /// different file is more likely to help than accepting the result.
+    #[default]
+    Insufficient,
+    /// Candidate sections were found, but all of them were dropped as too weak by
+    /// `min_total_matches`, `min_match_density` or another post-processing step. The file most
+    /// likely has no such section at all, for example an episode without an opening.
+    Rejected,
+}
+
+impl AnalysisStatus {
+    fn new(found: bool, enough: bool, rejected: bool) -> Self {
+        match (found, enough, rejected) {
+            (true, _, _) => Self::Found,
+            (false, false, _) => Self::Insufficient,
+            (false, true, true) => Self::Rejected,
+            (false, true, false) => Self::NoneFound,
+        }
+    }
+}
+
+impl Sections {
+    /// Returns the sections in seconds relative to the start of the file.
+    pub fn absolute(&self) -> Vec<(u128, u128)> {
+        self.sections
+            .iter()
+            .map(|(start, end)| (start + self.window_start, end + self.window_start))
+            .collect()
+    }
+
+    /// Returns by how many seconds the first section of `other` starts later than the first
+    /// section of `self`, in absolute time. Negative values mean `other` starts earlier.
+    pub fn offset_to(&self, other: &Sections) -> Option<i128> {
+        let this = self.absolute().first()?.0 as i128;
+        let other = other.absolute().first()?.0 as i128;
+
+        Some(other - this)
+    }
+
+    /// Returns the sections in absolute time along with their confidence, see
+    /// [`Quality::section_confidence`].
+    pub fn with_confidence(&self) -> Vec<((u128, u128), f64)> {
+        self.absolute()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, x)| {
+                let confidence = self.quality.section_confidence.get(idx);
+                (x, confidence.copied().unwrap_or(self.quality.confidence))
+            })
+            .collect()
+    }
+
+    /// Returns the section `preference` picks out of all sections, in absolute time.
+    pub fn preferred(&self, preference: SectionPreference) -> Option<(u128, u128)> {
+        preference.pick(self.with_confidence())
+    }
+
+    /// Returns the number of frames decoded from the file, the `analyzed` ones along with the
+    /// `ignored` ones. This is lower than expected for short files or when extraction failed
+    /// partway.
+    pub fn extracted(&self) -> usize {
+        self.analyzed + self.ignored.total()
+    }
+
+    /// Returns whether the section at `idx` only spans part of its reference section, such as an
+    /// opening cut short, `None` if its coverage isn't known.
+    pub fn is_partial(&self, idx: usize) -> Option<bool> {
+        self.coverage.get(idx).map(|x| *x < PARTIAL_MAX_COVERAGE)
+    }
+}
+
+/// Sections found by [`Sectionizer::categorize_windows`] in one of its windows.
+#[derive(Clone, Debug)]
+pub struct RegionSections {
+    /// Label of the window the sections were found in.
+    pub label: String,
+    pub sections1: Sections,
+    pub sections2: Sections,
+}
+
+/// Returns a progress callback for `compute_frame_vec` which forwards to `events`, if any.
+fn progress_reporter<'a>(
+    events: Option<&'a mpsc::Sender<SectionizerEvent>>,
+    file: &'a str,
+) -> impl Fn(u64) + 'a {
+    move |frames| {
+        if let Some(events) = events {
+            let _ = events.try_send(SectionizerEvent::Progress {
+                file: file.to_string(),
+                frames,
+            });
+        }
+    }
+}
+
+/// Builds the `Sections` of frames matched by `categorize_frames`, `enough` telling whether both
+/// sides had enough frames for an empty result to be trusted.
+fn frame_sections(
+    (sections, quality): (Vec<(u128, u128)>, Quality),
+    analyzed: usize,
+    fps: f64,
+    enough: bool,
+) -> Sections {
+    Sections {
+        status: AnalysisStatus::new(!sections.is_empty(), enough, quality.rejected > 0),
+        sections,
+        quality,
+        analyzed,
+        fps,
+        ..Default::default()
+    }
+}
+
+/// Reads the images of `dir` ordered by the number in their file name, scaled to `frame_size` and
+/// concatenated as raw RGB24 frames.
+fn read_image_dir(dir: &std::path::Path, frame_size: (u32, u32)) -> Result<Vec<u8>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|x| x.map(|x| x.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|x| x.is_file() && image::ImageFormat::from_path(x).is_ok());
+
+    let number = |path: &std::path::PathBuf| {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let digits = stem
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>();
+        digits.parse::<u64>().ok()
+    };
+    paths.sort_by_cached_key(|x| (number(x), x.clone()));
+
+    let (width, height) = frame_size;
+    let mut raw = Vec::with_capacity(paths.len() * width as usize * height as usize * 3);
+
+    for path in paths {
+        let img = image::open(&path).map_err(|e| {
+            SectionizerError::ImageFailed(path.display().to_string(), e.to_string())
+        })?;
+
+        let img = image::imageops::resize(
+            &img.to_rgb8(),
+            width,
+            height,
+            image::imageops::FilterType::CatmullRom,
+        );
+        raw.extend_from_slice(img.as_raw());
+    }
+
+    Ok(raw)
+}
+
+/// Returns a slog logger forwarding every record to the `log` facade.
+#[cfg(feature = "log")]
+fn log_facade() -> slog::Logger {
+    use slog::Drain;
+
+    slog::Logger::root(slog_stdlog::StdLog.fuse(), slog::o!())
+}
+
+/// Tells `events`, if any, that extraction has started for `file1` and `file2`.
+async fn send_started(events: Option<&mpsc::Sender<SectionizerEvent>>, file1: &str, file2: &str) {
+    if let Some(events) = events {
+        let _ = events
+            .send(SectionizerEvent::Started {
+                file1: file1.to_string(),
+                file2: file2.to_string(),
+            })
+            .await;
+    }
+}
+
+/// Returns whether `input` is a URL such as `https://host/file.mkv` rather than a local path.
+fn is_url(input: &str) -> bool {
+    match input.split_once("://") {
+        Some((scheme, _)) => {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|x| x.is_ascii_alphanumeric() || "+-.".contains(x))
+        }
+        None => false,
+    }
+}
+
+/// Returns the arguments nightfall runs ffmpeg with for `stream` of `file`, with frames scaled
+/// down to `(width, height)` instead, after being deinterlaced with `filter` and tonemapped to SDR
+/// with `TONEMAP_FILTERS` if set.
+fn direct_args(
+    stream: StreamType,
+    file: &str,
+    filter: Option<DeinterlaceFilter>,
+    tonemap: bool,
+    (width, height): (u32, u32),
+) -> Vec<String> {
+    let (map, profile, tt, sseof) = match stream {
+        StreamType::RawVideo {
+            map,
+            profile,
+            tt,
+            sseof,
+        } => (map, profile, tt, sseof),
+        _ => unreachable!("frames are only ever extracted as raw video"),
+    };
+
+    // ffmpeg's stderr is only read once it exits, progress output would fill up the pipe.
+    let mut args = ["-v", "error", "-nostats", "-fflags", "+genpts", "-y"]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    if let Some(sseof) = sseof {
+        args.extend(["-sseof".into(), (-sseof).to_string()]);
+    }
+
+    args.extend([
+        "-i".into(),
+        file.to_string(),
+        "-map".into(),
+        format!("0:{}", map),
+    ]);
+
+    if let (Some(tt), None) = (tt, sseof) {
+        args.extend(["-t".into(), tt.to_string()]);
+    }
+
+    let scale = format!("scale={}:{}", width, height);
+    let graph = |filters: &str| {
+        let filters = filters
+            .split(',')
+            .map(|x| match x.starts_with("scale=") {
+                true => scale.as_str(),
+                false => x,
+            })
+            .filter(|x| !x.is_empty());
+
+        filter
+            .map(|x| x.name())
+            .into_iter()
+            .chain(tonemap.then_some(TONEMAP_FILTERS))
+            .chain(filters)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    let mut profile = profile.to_args(0, "");
+    match profile.iter().position(|x| x == "-vf") {
+        Some(pos) if pos + 1 < profile.len() => profile[pos + 1] = graph(&profile[pos + 1]),
+        _ => profile.extend(["-vf".into(), graph(&scale)]),
+    }
+
+    args.extend(profile);
+    args
+}
+
+/// Makes sure the frames ffmpeg emits under `profile` are exactly `IMG_SIZE` bytes of packed
+/// `rgb24`, the layout `RgbImage::from_raw` expects. Neither a size mismatch nor swapped channels
+/// would fail any read, they would silently misalign frames or shift every hash, so they are
+/// caught before ffmpeg is spawned instead.
+fn check_profile(profile: &RawVideoProfile) -> Result<()> {
+    let args = profile.to_args(0, "");
+    let arg = |name: &str| {
+        args.iter()
+            .position(|x| x == name)
+            .and_then(|x| args.get(x + 1))
+            .map(String::as_str)
+    };
+
+    let dimensions = arg("-vf")
+        .and_then(|x| x.split(',').find_map(|x| x.strip_prefix("scale=")))
+        .and_then(|x| x.split_once(':'))
+        .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)));
+
+    // without a pinned format ffmpeg keeps the one of the input, which may be `bgr24` or planar.
+    match arg("-pix_fmt") {
+        Some("rgb24") => {}
+        pix_fmt => {
+            return Err(SectionizerError::PixelFormatMismatch(
+                profile.to_string(),
+                pix_fmt.unwrap_or("none").to_string(),
+            ))
+        }
+    }
+
+    let size = match dimensions {
+        Some((w, h)) => w * h * 3,
+        // without a fixed size ffmpeg keeps the one of the input, which is never what we expect.
+        None => 0,
+    };
+
+    if size != IMG_SIZE {
+        return Err(SectionizerError::FrameSizeMismatch(
+            profile.to_string(),
+            size,
+            IMG_SIZE,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the distance between `a` and `b` under `similarity`. Frames without a histogram are
+/// compared by their hash alone.
+fn distance(similarity: Similarity, a: &Frame, b: &Frame) -> isize {
+    let histogram = match (&a.histogram, &b.histogram) {
+        (Some(a), Some(b)) => a.distance(b),
+        _ => return hamming(a, b),
+    };
+
+    match similarity {
+        Similarity::Hash => hamming(a, b),
+        Similarity::Histogram => histogram,
+        Similarity::Combined { histogram_weight } => {
+            hamming(a, b) + (histogram as f64 * histogram_weight).round() as isize
+        }
+    }
+}
+
+pub fn hamming(a: &Frame, b: &Frame) -> isize {
+    a.hash.distance(&b.hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sectionizer(config: SectionizerConfig) -> Sectionizer {
+        Sectionizer::without_ffmpeg(slog::Logger::root(slog::Discard, slog::o!()), config)
+    }
+
+    /// Pseudo random hash for `seed`, so that frames with different seeds are far apart.
+    fn hash(seed: u64) -> FrameHash {
+        let mut x = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        FrameHash::Bits64(x ^ (x >> 31))
+    }
+
+    /// Frames of a 30 second file at 24 fps with the same 15 seconds of shared frames starting
+    /// at `start` seconds, every other frame is unique to the file.
+    fn episode(start: u64, file: u64) -> Vec<Frame> {
+        shared(start * 24, 15 * 24, file)
+    }
+
+    /// Frames of a 30 second file at 24 fps with `len` shared frames starting at frame `start`.
+    fn shared(start: u64, len: u64, file: u64) -> Vec<Frame> {
+        let end = start + len;
+
+        (0..720)
+            .map(|idx| match idx >= start && idx < end {
+                true => Frame::new(hash(idx - start), idx),
+                false => Frame::new(hash((file << 32) + idx), idx),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn categorize_frames_is_deterministic() {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+        let (frames1, frames2) = (episode(5, 1), episode(10, 2));
+
+        let run = || sectionizer.categorize_frames(frames1.clone(), frames2.clone(), 24.0, 24.0);
+
+        let first = run();
+        assert!(!first.0.sections.is_empty() && !first.1.sections.is_empty());
+
+        let first = serde_json::to_string(&first).unwrap();
+        for _ in 0..4 {
+            assert_eq!(serde_json::to_string(&run()).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn hashes_fit_their_width() {
+        let algs = [
+            img_hash::HashAlg::Mean,
+            img_hash::HashAlg::Gradient,
+            img_hash::HashAlg::VertGradient,
+            img_hash::HashAlg::DoubleGradient,
+            img_hash::HashAlg::Blockhash,
+        ];
+
+        for width in [HashWidth::Bits64, HashWidth::Bits128, HashWidth::Bits256] {
+            for hash_alg in algs {
+                let sectionizer = sectionizer(SectionizerConfig {
+                    hash_width: width,
+                    hash_alg,
+                    ..Default::default()
+                });
+
+                // `img_hash` panics when an algorithm rounds the size up past the width.
+                let hash = sectionizer.hasher()(&frame(true));
+                assert_eq!(hash.width(), width);
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_pads_short_input() {
+        let hash = FrameHash::from_bytes(HashWidth::Bits128, &[0xff; 4]);
+        assert_eq!(hash, FrameHash::Bits128(0xffff_ffff << 96));
+        assert_eq!(
+            FrameHash::from_bytes(HashWidth::Bits64, &[0x01; 12]),
+            FrameHash::Bits64(0x0101_0101_0101_0101)
+        );
+    }
+
+    #[test]
+    fn nearest_bounds_at_max_dist() {
+        let nearest = |distance_bound: DistanceBound, hash: u64| {
+            let sectionizer = sectionizer(SectionizerConfig {
+                distance_bound,
+                ..Default::default()
+            });
+            let tree = sectionizer.tree_from_vec(vec![Frame::new(FrameHash::Bits64(0), 0)]);
+            let frame = Frame::new(FrameHash::Bits64(hash), 1);
+
+            sectionizer
+                .nearest(&tree, frame, HASH_MAX_DIST)
+                .into_iter()
+                .map(|(_, dist)| dist)
+                .collect::<Vec<_>>()
+        };
+
+        // `0b11` is exactly `HASH_MAX_DIST` away, `0b1` just below it.
+        assert_eq!(nearest(DistanceBound::Inclusive, 0b11), vec![2]);
+        assert!(nearest(DistanceBound::Exclusive, 0b11).is_empty());
+        assert_eq!(nearest(DistanceBound::Inclusive, 0b1), vec![1]);
+        assert_eq!(nearest(DistanceBound::Exclusive, 0b1), vec![1]);
+        assert!(nearest(DistanceBound::Inclusive, 0b111).is_empty());
+    }
+
+    /// Red, green and blue thirds from left to right, swapping red and blue if `bgr`.
+    fn tricolour(bgr: bool) -> image::RgbImage {
+        image::RgbImage::from_fn(IMG_W as u32, IMG_H as u32, |x, _| {
+            let mut px = [0; 3];
+            px[x as usize * 3 / IMG_W] = 255;
+            if bgr {
+                px.reverse();
+            }
+            image::Rgb(px)
+        })
+    }
+
+    #[tokio::test]
+    async fn raw_frames_are_read_as_rgb() {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+
+        // packed rgb24 as ffmpeg emits it, every pixel is its red, green and blue bytes in order.
+        let raw = (0..IMG_H * IMG_W)
+            .flat_map(|i| {
+                let mut px = [0u8; 3];
+                px[(i % IMG_W) * 3 / IMG_W] = 255;
+                px
+            })
+            .collect::<Vec<_>>();
+
+        let (frames, _) = sectionizer
+            .compute_frame_vec("raw", raw.as_slice(), None, |_| {}, |_| false)
+            .await;
+
+        let hasher = sectionizer.hasher();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].hash(), hasher(&tricolour(false)));
+        assert_ne!(frames[0].hash(), hasher(&tricolour(true)));
+    }
+
+    #[test]
+    fn classify_black_by_luma() {
+        let frame = |px: [u8; 3]| px.repeat(IMG_W * IMG_H);
+
+        // a dark red has a luma of 12, though its red channel is well above the threshold.
+        assert_eq!(
+            IgnoreReason::classify(&frame([40, 0, 0]), (8, 16)),
+            Some(IgnoreReason::Black)
+        );
+        assert_eq!(
+            IgnoreReason::classify(&frame([40, 0, 0]), (8, 8)),
+            Some(IgnoreReason::Solid)
+        );
+        assert_eq!(
+            IgnoreReason::classify(&frame([8; 3]), (0, 8)),
+            Some(IgnoreReason::Black)
+        );
+        assert_eq!(
+            IgnoreReason::classify(&tricolour(false).into_raw(), (8, 8)),
+            None
+        );
+    }
+
+    /// Sections of two files sharing `len` frames, starting at frame `start1` and `start2`.
+    fn shared_sections(start1: u64, start2: u64, len: u64) -> (Vec<(u128, u128)>, usize) {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+        let (a, _) = sectionizer.categorize_frames(
+            shared(start1, len, 1),
+            shared(start2, len, 2),
+            24.0,
+            24.0,
+        );
+
+        let buckets = sectionizer
+            .detect(
+                sectionizer.tree_from_vec(shared(start2, len, 2)),
+                shared(start1, len, 1),
+                sectionizer.params(HASH_MAX_DIST, 24.0),
+            )
+            .sections
+            .iter()
+            .map(|x| x.buckets.len())
+            .sum();
+
+        (a.sections, buckets)
+    }
+
+    #[test]
+    fn single_bucket_sections_span_the_bucket() {
+        // frames 120 to 143 are exactly the bucket of the fifth second.
+        assert_eq!(shared_sections(120, 240, 24), (vec![(5, 6)], 1));
+    }
+
+    #[test]
+    fn two_bucket_sections() {
+        assert_eq!(shared_sections(120, 240, 2 * 24), (vec![(5, 7)], 2));
+        // a second of frames straddling two buckets is as wide as a single bucket.
+        assert_eq!(shared_sections(132, 252, 24), (vec![(5, 6)], 2));
+    }
+
+    #[test]
+    fn many_bucket_sections() {
+        assert_eq!(shared_sections(120, 240, 10 * 24), (vec![(5, 15)], 10));
+    }
+
+    #[test]
+    fn masks_cover_every_second_of_multi_bucket_sections() {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+        let (sections, _) =
+            sectionizer.categorize_frames(shared(120, 240, 1), shared(240, 240, 2), 24.0, 24.0);
+        assert_eq!(sections.sections, vec![(5, 15)]);
+
+        let mask = sections.to_mask();
+        assert!((5..15).all(|x| mask.contains(x)));
+        assert!(!mask.contains(4) && !mask.contains(15));
+    }
+
+    #[test]
+    fn evaluate_scores_an_exact_detection_fully() {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+        let (sections, _) =
+            sectionizer.categorize_frames(shared(120, 240, 1), shared(240, 240, 2), 24.0, 24.0);
+
+        let report = eval::evaluate(&sections, &[(5, 15)]);
+        assert_eq!(
+            (report.iou, report.precision, report.recall),
+            (1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn section_widths_grow_with_their_buckets() {
+        let width = |len: u64| {
+            let (sections, _) = shared_sections(120, 240, len * 24);
+            sections[0].1 - sections[0].0
+        };
+
+        assert_eq!([width(1), width(2), width(10)], [1, 2, 10]);
+    }
+
+    #[test]
+    fn get_groups_without_frames() {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+        let tree = sectionizer.tree_from_vec(Vec::new());
+        let params = sectionizer.params(HASH_MAX_DIST, 24.0);
+
+        let grouping = sectionizer.get_groups(&tree, Vec::new(), params);
+
+        assert!(grouping.groups.is_empty());
+        assert!(grouping.distances.is_empty());
+        assert_eq!(grouping.rejected, 0);
+    }
+
+    #[test]
+    fn get_groups_with_a_single_frame() {
+        let sectionizer = sectionizer(SectionizerConfig::default());
+        let frame = Frame::new(hash(0), 0);
+        let tree = sectionizer.tree_from_vec(vec![frame]);
+        let params = sectionizer.params(HASH_MAX_DIST, 24.0);
+
+        // a lone frame can't fill a bucket, whether or not it matches.
+        let grouping = sectionizer.get_groups(&tree, vec![frame], params);
+        assert!(grouping.groups.is_empty());
+
+        let grouping = sectionizer.get_groups(&tree, vec![Frame::new(hash(1), 0)], params);
+        assert!(grouping.groups.is_empty());
+        assert!(grouping.distances.is_empty());
+    }
+
+    /// A frame with a diagonal gradient, plus a bright logo in its top left corner if `logo`.
+    fn frame(logo: bool) -> image::RgbImage {
+        image::RgbImage::from_fn(IMG_W as u32, IMG_H as u32, |x, y| {
+            if logo && x < 4 && y < 4 {
+                image::Rgb([255; 3])
+            } else {
+                image::Rgb([(x * 12 + y * 4) as u8; 3])
+            }
+        })
+    }
+
+    #[test]
+    fn crop_ignores_corner_overlays() {
+        let distance = |crop_margin: f32| {
+            let sectionizer = sectionizer(SectionizerConfig {
+                crop_margin,
+                ..Default::default()
+            });
+            let hasher = sectionizer.hasher();
+            let a = hasher(&sectionizer.crop(frame(false)));
+            let b = hasher(&sectionizer.crop(frame(true)));
+
+            a.distance(&b)
+        };
+
+        assert!(distance(0.0) > HASH_MAX_DIST);
+        assert!(distance(0.2) <= HASH_MAX_DIST);
+    }
 }
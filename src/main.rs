@@ -1,10 +1,10 @@
 #![feature(box_syntax, slice_group_by)]
 
-use nightfall::*;
-
 use slog::o;
 use slog::Drain;
 
+use sectionizer::config::SectionizerConfig;
+use sectionizer::time::TimeFormat;
 use sectionizer::Sectionizer;
 
 #[tokio::main]
@@ -14,44 +14,161 @@ async fn main() {
     let drain = slog_async::Async::new(drain).build().fuse();
     let logger = slog::Logger::root(drain, o!());
 
-    let mut args = std::env::args();
+    let (flags, args): (Vec<String>, Vec<String>) =
+        std::env::args().skip(1).partition(|x| x.starts_with("--"));
+
+    let selftest = flags.iter().any(|x| x == "--selftest");
 
-    if args.len() < 5 {
+    if args.len() < 4 && !(selftest && args.len() >= 2) {
         slog::error!(
             logger,
-            "Usage: sectionizer <path to ffmpeg> <path to tmp> <target> <reference>"
+            "Usage: sectionizer [--offset] [--time-format=<format>] <path to ffmpeg> <path to tmp> \
+             <target> <reference>"
+        );
+        slog::error!(
+            logger,
+            "       sectionizer --selftest <path to ffmpeg> <path to tmp>"
+        );
+        slog::error!(
+            logger,
+            "<format> is one of mm:ss (default), hh:mm:ss, hh:mm:ss.mmm and secs"
         );
         return;
     }
 
-    let _ = args.next();
+    let show_offset = flags.iter().any(|x| x == "--offset");
+
+    let time_format = match flags.iter().find_map(|x| x.strip_prefix("--time-format=")) {
+        Some(name) => match TimeFormat::from_name(name) {
+            Some(x) => x,
+            None => {
+                slog::error!(logger, "Unknown time format {}", name);
+                return;
+            }
+        },
+        None => TimeFormat::MinutesSeconds,
+    };
+
+    let mut args = args.into_iter();
 
     let ffmpeg = args.next().unwrap();
     let tmp = args.next().unwrap();
 
+    if selftest {
+        if !run_selftest(ffmpeg, tmp, &logger).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let file1 = args.next().unwrap();
     let file2 = args.next().unwrap();
 
-    let state = StateManager::new(
-        &mut xtra::spawn::Tokio::Global,
-        tmp.into(),
-        ffmpeg.into(),
-        logger.clone(),
+    let config = SectionizerConfig {
+        ffmpeg,
+        cache_dir: tmp,
+        ..Default::default()
+    };
+
+    let mut sectionizer = Sectionizer::from_config(logger.clone(), config);
+
+    let (sections1, sections2) = sectionizer.categorize(file1, file2, false).await.unwrap();
+    log_sections("target", &sections1, time_format, &logger);
+    log_sections("reference", &sections2, time_format, &logger);
+
+    if show_offset {
+        match sections1.offset_to(&sections2) {
+            Some(offset) => slog::info!(logger, "Reference is offset by {}s from target", offset),
+            None => slog::info!(logger, "No matching sections to compute an offset from"),
+        }
+    }
+}
+
+/// Generates a short test pattern with ffmpeg and matches it against itself, which exercises
+/// ffmpeg, nightfall and hashing end to end. Returns whether every step succeeded.
+async fn run_selftest(ffmpeg: String, tmp: String, logger: &slog::Logger) -> bool {
+    let sample = std::path::Path::new(&tmp).join("sectionizer_selftest.mp4");
+    let sample = sample.to_string_lossy().to_string();
+
+    let started = std::time::Instant::now();
+    let status = tokio::process::Command::new(&ffmpeg)
+        .args(["-y", "-v", "error", "-f", "lavfi", "-i"])
+        .arg("testsrc=duration=10:size=320x240:rate=24")
+        .args(["-pix_fmt", "yuv420p", &sample])
+        .status()
+        .await;
+
+    match status {
+        Ok(x) if x.success() => {}
+        Ok(x) => {
+            slog::error!(
+                logger,
+                "selftest: ffmpeg failed to generate a sample: {}",
+                x
+            );
+            return false;
+        }
+        Err(e) => {
+            slog::error!(logger, "selftest: failed to run {}: {}", ffmpeg, e);
+            return false;
+        }
+    }
+
+    slog::info!(
+        logger,
+        "selftest: generated sample in {:?}",
+        started.elapsed()
     );
 
-    let mut sectionizer = Sectionizer::new(logger.clone(), state);
+    let config = SectionizerConfig {
+        ffmpeg,
+        cache_dir: tmp,
+        ..Default::default()
+    };
 
-    let sections = sectionizer.categorize(file1, file2, false).await.unwrap();
-    log_sections(sections.0, &logger);
-    log_sections(sections.1, &logger);
+    let mut sectionizer = Sectionizer::from_config(logger.clone(), config);
+
+    let started = std::time::Instant::now();
+    let result = sectionizer
+        .categorize(sample.clone(), sample.clone(), false)
+        .await;
+    let _ = std::fs::remove_file(&sample);
+
+    match result {
+        Ok((sections, _)) if !sections.sections.is_empty() => {
+            slog::info!(
+                logger,
+                "selftest: extracted, hashed and matched the sample in {:?}",
+                started.elapsed()
+            );
+            slog::info!(logger, "selftest: ok");
+            true
+        }
+        Ok(_) => {
+            slog::error!(
+                logger,
+                "selftest: frames were extracted but the sample did not match itself"
+            );
+            false
+        }
+        Err(e) => {
+            slog::error!(logger, "selftest: extraction failed: {}", e);
+            false
+        }
+    }
 }
 
-fn log_sections(sections: sectionizer::Sections, logger: &slog::Logger) {
-    slog::info!(logger, "Sections for {}", sections.target);
+fn log_sections(
+    label: &str,
+    sections: &sectionizer::Sections,
+    format: TimeFormat,
+    logger: &slog::Logger,
+) {
+    slog::info!(logger, "Sections for {} ({})", label, sections.target);
 
-    for section in sections.sections {
-        let start_ts = format!("{:02}:{:02}", section.0 / 60, section.0 % 60);
-        let end_ts = format!("{:02}:{:02}", section.1 / 60, section.1 % 60);
+    for section in sections.absolute() {
+        let start_ts = format.format(section.0 as f64);
+        let end_ts = format.format(section.1 as f64);
         slog::info!(logger, "{} -> {}", start_ts, end_ts);
     }
 }
@@ -0,0 +1,140 @@
+//! Detection of scrolling end credits from the motion between frames.
+//!
+//! Every frame of scrolling credits differs from the previous one, so their hashes drift and
+//! hardly match anything, yet the sequence is easy to tell from the motion alone: the whole frame
+//! moves up by the same few rows from one frame to the next. Frames are decoded as small grayscale
+//! images at [`FPS`], the vertical shift between consecutive frames is estimated by finding the
+//! offset at which they line up best, and long runs of a steady shift are reported.
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+
+/// Width of the frames motion is estimated on.
+pub const WIDTH: usize = 32;
+/// Height of the frames motion is estimated on, taller than wide so that slow scrolling still
+/// moves them by whole rows.
+pub const HEIGHT: usize = 128;
+/// Number of frames decoded per second.
+pub const FPS: f64 = 4.0;
+/// Largest shift in rows looked for between two frames, a quick scroll at `FPS`.
+const MAX_SHIFT: usize = 24;
+/// Frames whose mean deviation from their average brightness is below this are too flat for
+/// their motion to mean anything, such as the black between credits and a post-credits scene.
+const MIN_CONTRAST: f32 = 6.0;
+/// Fraction of the difference between two frames as they are which has to remain at most once
+/// they are lined up for the shift to count as motion.
+const MAX_RESIDUAL: f32 = 0.5;
+/// Minimum duration in seconds of a run of steady scrolling to be reported.
+const MIN_SECS: f64 = 10.0;
+/// Longest gap in seconds without scrolling within a run, such as a card shown still.
+const MAX_GAP_SECS: f64 = 2.0;
+
+/// Returns the ffmpeg arguments decoding the first video stream of `file` into what
+/// [`read_frames`] expects, with `tt` and `sseof` bounding the window like for hashing.
+pub(crate) fn ffmpeg_args(file: &str, tt: Option<usize>, sseof: Option<i64>) -> Vec<String> {
+    // ffmpeg's stderr is only read once it exits, progress output would fill up the pipe.
+    let mut args = vec!["-v".to_string(), "error".into(), "-nostats".into()];
+
+    if let Some(sseof) = sseof {
+        args.extend(["-sseof".into(), (-sseof).to_string()]);
+    }
+
+    args.extend(["-i".into(), file.to_string(), "-map".into(), "0:v:0".into()]);
+
+    if let (Some(tt), None) = (tt, sseof) {
+        args.extend(["-t".into(), tt.to_string()]);
+    }
+
+    args.extend(["-an".into(), "-vf".into()]);
+    args.push(format!("fps={},scale={}:{}", FPS, WIDTH, HEIGHT));
+    args.extend(
+        ["-f", "rawvideo", "-pix_fmt", "gray", "-"]
+            .iter()
+            .map(ToString::to_string),
+    );
+    args
+}
+
+/// Reads all of `stream`, raw grayscale frames of [`WIDTH`] by [`HEIGHT`], into frames.
+pub(crate) async fn read_frames(stream: impl AsyncRead + Unpin) -> Vec<Vec<u8>> {
+    let mut stream = tokio::io::BufReader::new(stream);
+    let mut frames = Vec::new();
+    let mut buf = vec![0u8; WIDTH * HEIGHT];
+
+    while stream.read_exact(&mut buf).await.is_ok() {
+        frames.push(buf.clone());
+    }
+
+    frames
+}
+
+/// Returns the number of rows the content of `prev` moved by in `next`, positive for upwards
+/// motion, or `None` if the frames don't show the same content shifted vertically. Frames which
+/// don't move at all are `None` as well.
+pub fn vertical_shift(prev: &[u8], next: &[u8]) -> Option<i32> {
+    let mean = prev.iter().map(|x| *x as f32).sum::<f32>() / prev.len() as f32;
+    let contrast = prev.iter().map(|x| (*x as f32 - mean).abs()).sum::<f32>() / prev.len() as f32;
+    if contrast < MIN_CONTRAST {
+        return None;
+    }
+
+    // mean difference between row `y` of `next` and row `y + shift` of `prev`, over the rows
+    // both frames have.
+    let difference = |shift: i32| {
+        let rows = (0..HEIGHT as i32).filter(|y| (0..HEIGHT as i32).contains(&(y + shift)));
+        let (sum, count) = rows.fold((0u64, 0u64), |(sum, count), y| {
+            let next = &next[y as usize * WIDTH..][..WIDTH];
+            let prev = &prev[(y + shift) as usize * WIDTH..][..WIDTH];
+            let row = next.iter().zip(prev).map(|(a, b)| a.abs_diff(*b) as u64);
+
+            (sum + row.sum::<u64>(), count + WIDTH as u64)
+        });
+
+        sum as f32 / count.max(1) as f32
+    };
+
+    let still = difference(0);
+    let (shift, moved) = (-(MAX_SHIFT as i32)..=MAX_SHIFT as i32)
+        .map(|x| (x, difference(x)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    (shift != 0 && moved <= still * MAX_RESIDUAL).then_some(shift)
+}
+
+/// Returns the runs of steady scrolling in `frames`, consecutive frames decoded at [`FPS`], as the
+/// indices of their first and last frame. The shift has to keep its direction and stay close to
+/// the average shift of the run so far, a scroll changing speed is cut in two.
+pub fn scrolling_runs(frames: &[Vec<u8>]) -> Vec<(u64, u64)> {
+    let max_gap = (MAX_GAP_SECS * FPS).round() as u64;
+    let min_len = (MIN_SECS * FPS).round() as u64;
+
+    let mut runs = Vec::new();
+    // first and last frame of the current run along with the sum and count of its shifts.
+    let mut run: Option<(u64, u64, i64, i64)> = None;
+
+    for (idx, pair) in (0u64..).zip(frames.windows(2)) {
+        let shift = vertical_shift(&pair[0], &pair[1]);
+
+        if let (Some((first, last, sum, count)), Some(shift)) = (run, shift) {
+            let mean = sum as f32 / count as f32;
+            let steady = (shift as f32 - mean).abs() <= (mean.abs() / 2.0).max(2.0);
+
+            if steady && shift.signum() as f32 == mean.signum() && idx <= last + max_gap {
+                run = Some((first, idx + 1, sum + shift as i64, count + 1));
+                continue;
+            }
+        }
+
+        match run {
+            Some((first, last, ..)) if idx > last + max_gap || shift.is_some() => {
+                runs.push((first, last));
+                run = shift.map(|x| (idx, idx + 1, x as i64, 1));
+            }
+            None => run = shift.map(|x| (idx, idx + 1, x as i64, 1)),
+            _ => {}
+        }
+    }
+
+    runs.extend(run.map(|(first, last, ..)| (first, last)));
+    runs.retain(|(first, last)| last - first >= min_len);
+    runs
+}
@@ -0,0 +1,492 @@
+//! Steps applied to every section between grouping the matched frames and reporting it.
+//!
+//! [`SectionizerConfig::post_processors`](crate::config::SectionizerConfig::post_processors) holds
+//! the pipeline, which is run in order on the sections of every match. The built-in steps read
+//! their tunables from the configuration, so the default pipeline only does anything when those
+//! are set.
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::ScanDirection;
+use crate::config::SectionizerConfig;
+use crate::hamming;
+use crate::Frame;
+use crate::FLAT_HASH_MAX_ONES;
+use crate::SCENE_CUT_DIST;
+
+/// A bucket key along with the matched frames that fell into it.
+pub type Bucket = (u64, Vec<Frame>);
+
+/// A section as it goes through post-processing.
+#[derive(Clone, Debug)]
+pub struct Section {
+    /// Buckets of matched frames making up the section, sorted.
+    pub buckets: Vec<Bucket>,
+    /// Index of the first frame of the section.
+    pub first: u64,
+    /// Index of the last frame of the section.
+    pub last: u64,
+}
+
+impl Section {
+    /// Builds a section spanning the matched frames of `buckets`, `None` if there are none.
+    pub fn new(buckets: Vec<Bucket>) -> Option<Self> {
+        let idx = buckets.iter().flat_map(|(_, x)| x.iter().map(|x| x.idx));
+        let (first, last) = (idx.clone().min()?, idx.max()?);
+
+        Some(Self {
+            buckets,
+            first,
+            last,
+        })
+    }
+}
+
+/// Everything a [`SectionPostProcessor`] may need to know about the match its sections come from.
+pub struct Ctx<'a> {
+    pub config: &'a SectionizerConfig,
+    /// Every frame of the file the sections were found in, sorted.
+    pub frames: &'a [Frame],
+    /// Frame rate of the file.
+    pub fps: f64,
+    /// Number of frames hashed per second, lower than `fps` with a `hash_stride`.
+    pub hashed_fps: f64,
+    /// Duration of a bucket in seconds.
+    pub bucket_secs: f64,
+}
+
+/// A step of the post-processing pipeline.
+pub trait SectionPostProcessor: fmt::Debug + Send + Sync {
+    /// Returns `sections` after this step. Sections may be adjusted, dropped or added; the
+    /// returned ones are handed to the next step.
+    fn process(&self, sections: Vec<Section>, ctx: &Ctx) -> Vec<Section>;
+}
+
+/// Returns the built-in steps in the order they have always been applied in.
+pub fn default_pipeline() -> Vec<Arc<dyn SectionPostProcessor>> {
+    vec![
+        Arc::new(MinMatchDensity),
+        Arc::new(EdgeDensity),
+        Arc::new(MinRun),
+        Arc::new(RefineStart),
+        Arc::new(MergeSections),
+        Arc::new(ScanBackward),
+    ]
+}
+
+/// Drops every section if, across all of them, the fraction of frames which matched is below
+/// `min_match_density`. A file without the section still tends to produce a few scattered
+/// matches, this tells those apart from a real but imperfect match.
+#[derive(Clone, Copy, Debug)]
+pub struct MinMatchDensity;
+
+impl SectionPostProcessor for MinMatchDensity {
+    fn process(&self, sections: Vec<Section>, ctx: &Ctx) -> Vec<Section> {
+        let min_density = match ctx.config.min_match_density {
+            Some(x) => x,
+            None => return sections,
+        };
+
+        let matched = sections
+            .iter()
+            .flat_map(|x| x.buckets.iter())
+            .map(|(_, x)| x.len())
+            .sum::<usize>();
+        let total = ctx
+            .frames
+            .iter()
+            .filter(|x| sections.iter().any(|y| x.idx >= y.first && x.idx <= y.last))
+            .count();
+
+        if total == 0 || (matched as f64 / total as f64) < min_density {
+            return Vec::new();
+        }
+
+        sections
+    }
+}
+
+/// Drops the leading and trailing buckets of every section whose fraction of matched frames is
+/// below `edge_density`, so that sections only span the part the evidence supports. Sections
+/// without a single dense bucket are left as they are.
+///
+/// As this recomputes the bounds of a section from its buckets, it should run before any step
+/// moving those bounds.
+#[derive(Clone, Copy, Debug)]
+pub struct EdgeDensity;
+
+impl SectionPostProcessor for EdgeDensity {
+    fn process(&self, sections: Vec<Section>, ctx: &Ctx) -> Vec<Section> {
+        let min_density = match ctx.config.edge_density {
+            Some(x) => x,
+            None => return sections,
+        };
+
+        let bucket_frames = ctx.hashed_fps * ctx.bucket_secs;
+        let dense = |(_, x): &Bucket| x.len() as f64 / bucket_frames >= min_density;
+
+        sections
+            .into_iter()
+            .filter_map(|x| {
+                let buckets = &x.buckets;
+                match (
+                    buckets.iter().position(dense),
+                    buckets.iter().rposition(dense),
+                ) {
+                    (Some(first), Some(last)) => Section::new(buckets[first..=last].to_vec()),
+                    _ => Some(x),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the length in frames of the longest run of consecutive `frames` within `section` which
+/// all matched. `frames` are every hashed frame of the file, sorted, so a frame dropped before
+/// matching doesn't break a run while one which failed to match does.
+pub fn longest_run(section: &Section, frames: &[Frame]) -> usize {
+    let matched = section
+        .buckets
+        .iter()
+        .flat_map(|(_, x)| x.iter().map(|x| x.idx))
+        .collect::<BTreeSet<_>>();
+
+    let start = frames.partition_point(|x| x.idx < section.first);
+    let end = frames.partition_point(|x| x.idx <= section.last);
+
+    let (mut longest, mut run) = (0, 0);
+    for frame in &frames[start..end.max(start)] {
+        if matched.contains(&frame.idx) {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+
+    longest
+}
+
+/// Drops every section whose [`longest_run`] lasts less than `min_run_secs`.
+#[derive(Clone, Copy, Debug)]
+pub struct MinRun;
+
+impl SectionPostProcessor for MinRun {
+    fn process(&self, sections: Vec<Section>, ctx: &Ctx) -> Vec<Section> {
+        let min_secs = match ctx.config.min_run_secs {
+            Some(x) => x,
+            None => return sections,
+        };
+
+        sections
+            .into_iter()
+            .filter(|x| longest_run(x, ctx.frames) as f64 / ctx.hashed_fps >= min_secs)
+            .collect()
+    }
+}
+
+/// Walks the start of every section back for up to a second, as long as the preceding frames
+/// look like a continuation of the same shot, when `precise_boundaries` is set. The walk stops at
+/// a scene cut, at a flat frame such as the black a fade-in starts from, or at frames which were
+/// dropped before matching.
+///
+/// The adjustment is tapered by the density of matches in the second after the start: it is
+/// scaled down by it and skipped entirely below `boundary_min_confidence`, so that a weakly
+/// matched start is not pushed further into an ambiguous fade.
+#[derive(Clone, Copy, Debug)]
+pub struct RefineStart;
+
+impl RefineStart {
+    /// Returns the fraction of the frames in the first second of `section` which matched, a
+    /// measure of how much its start can be trusted.
+    fn confidence(&self, section: &Section, ctx: &Ctx) -> f64 {
+        let end = section.first + (ctx.fps.round() as u64).max(1);
+        let in_range = |idx: u64| idx >= section.first && idx < end;
+
+        let matched = section
+            .buckets
+            .iter()
+            .flat_map(|(_, x)| x.iter())
+            .filter(|x| in_range(x.idx))
+            .count();
+        let total = ctx.frames.iter().filter(|x| in_range(x.idx)).count();
+
+        if total == 0 {
+            return 0.0;
+        }
+
+        matched as f64 / total as f64
+    }
+
+    /// Returns the index of the frame a section starting at frame `first` should start at.
+    fn refine(&self, first: u64, confidence: f64, ctx: &Ctx) -> u64 {
+        if confidence < ctx.config.boundary_min_confidence {
+            return first;
+        }
+
+        let frames = ctx.frames;
+        let pos = match frames.binary_search_by_key(&first, |x| x.idx) {
+            Ok(pos) => pos,
+            Err(_) => return first,
+        };
+
+        let max_lookback = ctx.fps.round() as u64;
+        let mut start = first;
+
+        for pair in frames[..=pos].windows(2).rev() {
+            let (prev, next) = (&pair[0], &pair[1]);
+
+            if first - prev.idx > max_lookback
+                || next.idx - prev.idx > ctx.config.hash_stride.max(1)
+                || prev.hash.ones() <= FLAT_HASH_MAX_ONES
+                || hamming(prev, next) > SCENE_CUT_DIST
+            {
+                break;
+            }
+
+            start = prev.idx;
+        }
+
+        first - ((first - start) as f64 * confidence.min(1.0)).round() as u64
+    }
+}
+
+impl SectionPostProcessor for RefineStart {
+    fn process(&self, mut sections: Vec<Section>, ctx: &Ctx) -> Vec<Section> {
+        if !ctx.config.precise_boundaries {
+            return sections;
+        }
+
+        for section in sections.iter_mut() {
+            let confidence = self.confidence(section, ctx);
+            section.first = self.refine(section.first, confidence, ctx);
+        }
+
+        sections
+    }
+}
+
+/// Merges sections separated by at most `section_merge_gap_secs`, or overlapping, into one. This
+/// runs on sections in their final shape, so it comes after the steps adjusting them.
+#[derive(Clone, Copy, Debug)]
+pub struct MergeSections;
+
+impl SectionPostProcessor for MergeSections {
+    fn process(&self, mut sections: Vec<Section>, ctx: &Ctx) -> Vec<Section> {
+        let gap = match ctx.config.section_merge_gap_secs {
+            Some(x) => (x.max(0.0) * ctx.fps).round() as u64,
+            None => return sections,
+        };
+
+        sections.sort_by_key(|x| x.first);
+
+        let mut merged: Vec<Section> = Vec::with_capacity(sections.len());
+        for section in sections {
+            match merged.last_mut() {
+                Some(last) if section.first <= last.last + gap => {
+                    last.last = last.last.max(section.last);
+                    last.buckets.extend(section.buckets);
+                    last.buckets.sort_by_key(|(x, _)| *x);
+                }
+                _ => merged.push(section),
+            }
+        }
+
+        merged
+    }
+}
+
+/// Walks the frames of the window from its end back when `scan_direction` is
+/// [`ScanDirection::Backward`], and replaces the sections with the run of matched frames met
+/// first. The run goes on through up to `merge_gap_secs` worth of hashed frames which didn't
+/// match, frames dropped before matching don't count, so it may span several sections. Its start
+/// is the last matched frame reached, or where an earlier step moved the start of the section
+/// holding it. As it runs on sections in their final shape, it comes after [`MergeSections`].
+#[derive(Clone, Copy, Debug)]
+pub struct ScanBackward;
+
+impl SectionPostProcessor for ScanBackward {
+    fn process(&self, sections: Vec<Section>, ctx: &Ctx) -> Vec<Section> {
+        if ctx.config.scan_direction != ScanDirection::Backward {
+            return sections;
+        }
+
+        let matched = |x: &Section| {
+            x.buckets
+                .iter()
+                .flat_map(|(_, x)| x.iter().map(|x| x.idx))
+                .collect::<Vec<_>>()
+        };
+        let all = sections.iter().flat_map(matched).collect::<BTreeSet<_>>();
+        let max_gap = (ctx.config.merge_gap_secs * ctx.hashed_fps).round() as usize;
+
+        let (mut first, mut last, mut gap) = (None, None, 0);
+        for frame in ctx.frames.iter().rev() {
+            if all.contains(&frame.idx) {
+                last.get_or_insert(frame.idx);
+                first = Some(frame.idx);
+                gap = 0;
+            } else if last.is_some() {
+                gap += 1;
+                if gap > max_gap {
+                    break;
+                }
+            }
+        }
+
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+
+        let mut buckets = sections
+            .iter()
+            .flat_map(|x| x.buckets.iter())
+            .filter_map(|(key, x)| {
+                let frames = x
+                    .iter()
+                    .filter(|x| x.idx >= first && x.idx <= last)
+                    .copied()
+                    .collect::<Vec<_>>();
+                (!frames.is_empty()).then_some((*key, frames))
+            })
+            .collect::<Vec<_>>();
+        buckets.sort_by_key(|(x, _)| *x);
+
+        let bounds = |idx: u64| {
+            sections
+                .iter()
+                .find(|x| matched(x).contains(&idx))
+                .map(|x| (x.first, x.last))
+                .unwrap_or((idx, idx))
+        };
+
+        vec![Section {
+            buckets,
+            first: bounds(first).0.min(first),
+            last: bounds(last).1.max(last),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::FrameHash;
+
+    /// Bucket `key` of one second at 24 fps, with its first `matched` frames matched.
+    fn bucket(key: u64, matched: u64) -> Bucket {
+        let frames = (key * 24..key * 24 + matched)
+            .map(|idx| Frame::new(FrameHash::Bits64(idx), idx))
+            .collect();
+
+        (key, frames)
+    }
+
+    /// Runs `step` on `sections` of a file of 10 seconds at 24 fps.
+    fn process(
+        step: impl SectionPostProcessor,
+        config: SectionizerConfig,
+        sections: Vec<Section>,
+    ) -> Vec<Section> {
+        let frames = (0..240)
+            .map(|idx| Frame::new(FrameHash::Bits64(idx), idx))
+            .collect::<Vec<_>>();
+        let ctx = Ctx {
+            config: &config,
+            frames: &frames,
+            fps: 24.0,
+            hashed_fps: 24.0,
+            bucket_secs: 1.0,
+        };
+
+        step.process(sections, &ctx)
+    }
+
+    fn edge_density(buckets: Vec<Bucket>) -> Vec<Section> {
+        let config = SectionizerConfig {
+            edge_density: Some(0.5),
+            ..Default::default()
+        };
+
+        process(
+            EdgeDensity,
+            config,
+            Section::new(buckets).into_iter().collect(),
+        )
+    }
+
+    #[test]
+    fn edge_density_trims_sparse_edges() {
+        let mut buckets = vec![bucket(0, 2), bucket(1, 3)];
+        buckets.extend((2..8).map(|x| bucket(x, 20)));
+        buckets.push(bucket(8, 2));
+
+        let sections = edge_density(buckets);
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!((sections[0].first, sections[0].last), (48, 7 * 24 + 19));
+        assert_eq!(sections[0].buckets.len(), 6);
+    }
+
+    #[test]
+    fn edge_density_keeps_sections_without_dense_buckets() {
+        let sections = edge_density((0..4).map(|x| bucket(x, 2)).collect());
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!((sections[0].first, sections[0].last), (0, 3 * 24 + 1));
+        assert_eq!(sections[0].buckets.len(), 4);
+    }
+
+    fn scan_backward(sections: Vec<Section>) -> Vec<Section> {
+        let config = SectionizerConfig {
+            scan_direction: ScanDirection::Backward,
+            merge_gap_secs: 1.0,
+            ..Default::default()
+        };
+
+        process(ScanBackward, config, sections)
+    }
+
+    #[test]
+    fn scan_backward_starts_where_the_last_run_begins() {
+        // frames 84 to 95 didn't match, half a second which the run goes on through, while the
+        // 28 frames between both sections end it.
+        let sections = vec![
+            Section::new(vec![bucket(0, 20)]).unwrap(),
+            Section::new(vec![
+                bucket(2, 24),
+                bucket(3, 12),
+                bucket(4, 24),
+                bucket(5, 24),
+            ])
+            .unwrap(),
+        ];
+
+        let sections = scan_backward(sections);
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!((sections[0].first, sections[0].last), (48, 6 * 24 - 1));
+        assert_eq!(sections[0].buckets.len(), 4);
+    }
+
+    #[test]
+    fn scan_backward_keeps_a_refined_start() {
+        let mut section = Section::new(vec![bucket(4, 24), bucket(5, 24)]).unwrap();
+        section.first -= 10;
+
+        let sections = scan_backward(vec![section, Section::new(vec![bucket(0, 4)]).unwrap()]);
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!(
+            (sections[0].first, sections[0].last),
+            (4 * 24 - 10, 6 * 24 - 1)
+        );
+    }
+
+    #[test]
+    fn scan_backward_without_matches() {
+        assert!(scan_backward(Vec::new()).is_empty());
+    }
+}
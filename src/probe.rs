@@ -0,0 +1,170 @@
+//! Thin wrappers around `ffprobe` for the bits of container metadata we need.
+use crate::error::SectionizerError;
+use crate::Result;
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tokio::process::Command;
+
+/// Returns the duration of `file` in seconds as reported by the container.
+pub(crate) async fn duration(ffprobe: &str, file: &str) -> Result<f64> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(file)
+        .output()
+        .await?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| SectionizerError::ProbeFailed(file.to_string()))
+}
+
+/// Returns whether the first video stream of `file` is interlaced, going by its field order.
+pub(crate) async fn interlaced(ffprobe: &str, file: &str) -> Result<bool> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=field_order"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(file)
+        .output()
+        .await?;
+
+    // `progressive` and `unknown` are taken at their word, the rest name the order of the fields.
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "" => Err(SectionizerError::ProbeFailed(file.to_string())),
+        order => Ok(matches!(order, "tt" | "bb" | "tb" | "bt")),
+    }
+}
+
+/// Returns the frame rate of the first video stream of `file`, going by its `r_frame_rate`.
+pub(crate) async fn frame_rate(ffprobe: &str, file: &str) -> Result<f64> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=r_frame_rate"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(file)
+        .output()
+        .await?;
+
+    parse_rate(String::from_utf8_lossy(&output.stdout).trim())
+        .ok_or_else(|| SectionizerError::ProbeFailed(file.to_string()))
+}
+
+/// Parses a rate like `24000/1001` or `25`, ffprobe reports `0/0` when it doesn't know.
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/').unwrap_or((rate, "1"));
+    let rate = num.parse::<f64>().ok()? / den.parse::<f64>().ok()?;
+
+    (rate.is_finite() && rate > 0.0).then_some(rate)
+}
+
+/// Returns whether the first video stream of `file` is HDR, going by its transfer function.
+pub(crate) async fn hdr(ffprobe: &str, file: &str) -> Result<bool> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=color_transfer"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(file)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(SectionizerError::ProbeFailed(file.to_string()));
+    }
+
+    // PQ and HLG, untagged streams report `unknown` and are taken to be SDR.
+    Ok(matches!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "smpte2084" | "arib-std-b67"
+    ))
+}
+
+#[derive(Deserialize)]
+struct ProbeStreams {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    #[serde(default)]
+    disposition: HashMap<String, i64>,
+}
+
+/// Returns whether `file` has a video stream, not counting cover art attached to audio files.
+pub(crate) async fn has_video(ffprobe: &str, file: &str) -> Result<bool> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-select_streams", "v"])
+        .args([
+            "-show_entries",
+            "stream=index:stream_disposition=attached_pic",
+        ])
+        .args(["-of", "json"])
+        .arg(file)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(SectionizerError::ProbeFailed(file.to_string()));
+    }
+
+    let probed: ProbeStreams = serde_json::from_slice(&output.stdout)?;
+
+    Ok(probed
+        .streams
+        .iter()
+        .any(|x| x.disposition.get("attached_pic") != Some(&1)))
+}
+
+/// A chapter of a file, with its bounds in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Chapter {
+    pub title: Option<String>,
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Deserialize)]
+struct ProbeChapters {
+    #[serde(default)]
+    chapters: Vec<ProbeChapter>,
+}
+
+#[derive(Deserialize)]
+struct ProbeChapter {
+    start_time: String,
+    end_time: String,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+/// Returns the chapters of `file` in order, which is empty if the file has none.
+pub(crate) async fn chapters(ffprobe: &str, file: &str) -> Result<Vec<Chapter>> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-show_chapters", "-of", "json"])
+        .arg(file)
+        .output()
+        .await?;
+
+    let probed: ProbeChapters = serde_json::from_slice(&output.stdout)?;
+
+    probed
+        .chapters
+        .into_iter()
+        .map(|x| {
+            let parse = |x: &str| {
+                x.parse::<f64>()
+                    .map_err(|_| SectionizerError::ProbeFailed(file.to_string()))
+            };
+
+            Ok(Chapter {
+                start: parse(&x.start_time)?,
+                end: parse(&x.end_time)?,
+                title: x.tags.get("title").cloned(),
+            })
+        })
+        .collect()
+}
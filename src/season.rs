@@ -0,0 +1,56 @@
+//! Helpers for combining the sections detected across the episodes of a season.
+use crate::eval::merge;
+use crate::Sections;
+
+/// Method `consensus_section` returns the single time range shared by the sections of (nearly)
+/// every episode of `results`, for example a canonical "skip intro" window for a whole show.
+///
+/// Sections are compared in absolute time and treated as half-open `[start, end)` ranges. A second
+/// is part of the consensus if it is covered by the sections of at least `quorum` (a fraction
+/// between `0.0` and `1.0`) of the episodes, so that a few episodes without an opening or with a
+/// bad detection don't shrink the result to nothing. Small differences in where the opening
+/// starts only trim the result to the part all of those episodes agree on.
+///
+/// # Returns
+/// The longest range meeting the quorum, preferring the earliest on ties, or `None` if there is
+/// none.
+pub fn consensus_section(results: &[Sections], quorum: f64) -> Option<(u128, u128)> {
+    let needed = ((quorum * results.len() as f64).ceil() as usize).max(1);
+
+    // every episode contributes each of its seconds at most once.
+    let mut events = results
+        .iter()
+        .flat_map(|x| merge(x.absolute()))
+        .flat_map(|(start, end)| vec![(start, 1), (end, -1)])
+        .collect::<Vec<(u128, isize)>>();
+
+    events.sort_unstable();
+
+    let mut best: Option<(u128, u128)> = None;
+    let mut run_start = None;
+    let mut count = 0isize;
+
+    for group in events.group_by(|a, b| a.0 == b.0) {
+        let at = group[0].0;
+        count += group.iter().map(|(_, delta)| delta).sum::<isize>();
+
+        match run_start {
+            None if count >= needed as isize => run_start = Some(at),
+            Some(start) if count < needed as isize => {
+                run_start = None;
+
+                let longer = match best {
+                    Some((a, b)) => at - start > b - a,
+                    None => true,
+                };
+
+                if longer {
+                    best = Some((start, at));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    best
+}
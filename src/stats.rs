@@ -0,0 +1,78 @@
+//! Counters describing the work done by a [`Sectionizer`](crate::Sectionizer), for monitoring.
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// Counters shared by a [`Sectionizer`](crate::Sectionizer) and all of its clones. They are only
+/// ever incremented and can be read at any time, even while the `Sectionizer` is busy.
+#[derive(Debug, Default)]
+pub struct Stats {
+    frames_decoded: AtomicU64,
+    bytes_read: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    comparisons: AtomicU64,
+    matching_nanos: AtomicU64,
+}
+
+impl Stats {
+    /// Number of frames read from ffmpeg, including frames which were ignored.
+    pub fn frames_decoded(&self) -> u64 {
+        self.frames_decoded.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes of raw frames read from ffmpeg.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// Number of fingerprints found in a [`FingerprintStore`](crate::fingerprint::FingerprintStore),
+    /// and of files whose frames were found in the frame [`cache`](crate::cache).
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of fingerprints looked up in a
+    /// [`FingerprintStore`](crate::fingerprint::FingerprintStore) but not found, and of files
+    /// whose frames had to be extracted as they weren't in the frame [`cache`](crate::cache).
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses.load(Ordering::Relaxed)
+    }
+
+    /// Number of times a set of frames was matched against another.
+    pub fn comparisons(&self) -> u64 {
+        self.comparisons.load(Ordering::Relaxed)
+    }
+
+    /// Total time spent matching frames.
+    pub fn matching_time(&self) -> Duration {
+        Duration::from_nanos(self.matching_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Average time spent on a single comparison, `None` if there were none yet.
+    pub fn average_matching_time(&self) -> Option<Duration> {
+        match self.comparisons() {
+            0 => None,
+            x => Some(self.matching_time() / x as u32),
+        }
+    }
+
+    pub(crate) fn add_frame(&self, bytes: usize) {
+        self.frames_decoded.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_cache_lookup(&self, hit: bool) {
+        if hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add_comparison(&self, elapsed: Duration) {
+        self.comparisons.fetch_add(1, Ordering::Relaxed);
+        self.matching_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,77 @@
+//! Conversions between frame indices and timestamps.
+//!
+//! Frame rates are taken as floating point numbers so that NTSC rates such as `23.976` are exact
+//! enough, `fps` has to be positive in all of these.
+use std::time::Duration;
+
+/// Slack applied before truncating to a frame index, so that the timestamp of a frame maps back
+/// to that same frame despite rounding errors with fractional frame rates.
+const FRAME_EPSILON: f64 = 1e-6;
+
+/// Returns the timestamp in seconds at which frame `idx` is shown.
+pub fn frame_to_secs(idx: u64, fps: f64) -> f64 {
+    idx as f64 / fps
+}
+
+/// Returns the index of the frame shown at `secs` seconds, `0` for negative timestamps.
+pub fn secs_to_frame(secs: f64, fps: f64) -> u64 {
+    (secs * fps + FRAME_EPSILON).floor().max(0.0) as u64
+}
+
+/// Returns the timestamp at which frame `idx` is shown.
+pub fn frame_to_duration(idx: u64, fps: f64) -> Duration {
+    Duration::from_secs_f64(frame_to_secs(idx, fps))
+}
+
+/// Returns the index of the frame shown at `duration`.
+pub fn duration_to_frame(duration: Duration, fps: f64) -> u64 {
+    secs_to_frame(duration.as_secs_f64(), fps)
+}
+
+/// Format timestamps are printed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// `MM:SS`, minutes going past `59` for timestamps of an hour or more.
+    MinutesSeconds,
+    /// `HH:MM:SS`.
+    HoursMinutesSeconds,
+    /// `HH:MM:SS.mmm`.
+    HoursMinutesSecondsMillis,
+    /// Seconds as a plain number, with a fractional part only if there is one.
+    Seconds,
+}
+
+impl TimeFormat {
+    /// Returns the format named `name`, one of `mm:ss`, `hh:mm:ss`, `hh:mm:ss.mmm` and `secs`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "mm:ss" => Some(Self::MinutesSeconds),
+            "hh:mm:ss" => Some(Self::HoursMinutesSeconds),
+            "hh:mm:ss.mmm" => Some(Self::HoursMinutesSecondsMillis),
+            "secs" => Some(Self::Seconds),
+            _ => None,
+        }
+    }
+
+    /// Returns `secs` formatted, negative timestamps are printed as `0`.
+    pub fn format(&self, secs: f64) -> String {
+        let millis = (secs.max(0.0) * 1000.0).round() as u128;
+        let (secs, millis) = (millis / 1000, millis % 1000);
+
+        match self {
+            Self::MinutesSeconds => format!("{:02}:{:02}", secs / 60, secs % 60),
+            Self::HoursMinutesSeconds => {
+                format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+            }
+            Self::HoursMinutesSecondsMillis => format!(
+                "{:02}:{:02}:{:02}.{:03}",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60,
+                millis
+            ),
+            Self::Seconds if millis == 0 => secs.to_string(),
+            Self::Seconds => format!("{}.{:03}", secs, millis),
+        }
+    }
+}